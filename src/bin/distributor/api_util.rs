@@ -1,14 +1,60 @@
 use crate::state_actor::{ServiceNotFoundError, WriteError};
 use axum::{
-    http::StatusCode,
+    async_trait,
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{header, request::Parts, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use std::{error::Error, fmt::Display};
+use swec::ApiErrorBody;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub enum ApiError {
     WriteError(WriteError),
     ServiceNotFoundError,
+    GroupNotFound,
+    Unauthorized,
+    MissingIfMatch,
+    BatchTooLarge,
+    /// Too many writes to the service, which can be retried after `retry_after` seconds.
+    RateLimited {
+        retry_after: u64,
+    },
+    /// The body, query string or path of the request couldn't be extracted.
+    InvalidRequest {
+        status: StatusCode,
+        detail: String,
+    },
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(value: JsonRejection) -> Self {
+        Self::InvalidRequest {
+            status: value.status(),
+            detail: value.body_text(),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(value: QueryRejection) -> Self {
+        Self::InvalidRequest {
+            status: value.status(),
+            detail: value.body_text(),
+        }
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(value: PathRejection) -> Self {
+        Self::InvalidRequest {
+            status: value.status(),
+            detail: value.body_text(),
+        }
+    }
 }
 
 impl From<WriteError> for ApiError {
@@ -28,22 +74,144 @@ impl Display for ApiError {
         match self {
             Self::WriteError(e) => e.fmt(f),
             Self::ServiceNotFoundError => ServiceNotFoundError.fmt(f),
+            Self::GroupNotFound => write!(f, "Group not found"),
+            Self::Unauthorized => write!(f, "Missing or invalid API key"),
+            Self::MissingIfMatch => write!(f, "Missing If-Match header"),
+            Self::BatchTooLarge => write!(f, "Too many entries in batch"),
+            Self::RateLimited { retry_after } => write!(
+                f,
+                "Too many writes to this service, retry in {retry_after} seconds"
+            ),
+            Self::InvalidRequest { .. } => write!(f, "Invalid request"),
         }
     }
 }
 
 impl Error for ApiError {}
 
-impl IntoResponse for ApiError {
-    fn into_response(self) -> Response {
+impl ApiError {
+    /// Get the machine-readable identifier of the error.
+    const fn code(&self) -> &'static str {
         match self {
-            Self::WriteError(WriteError::NameConflict) => {
-                (StatusCode::CONFLICT, WriteError::NameConflict.to_string())
+            Self::WriteError(WriteError::NameConflict) => "name_conflict",
+            Self::WriteError(WriteError::ReservedName) => "reserved_name",
+            Self::ServiceNotFoundError | Self::WriteError(WriteError::NotFound) => {
+                "service_not_found"
             }
+            Self::WriteError(WriteError::InvalidSpec(_)) => "invalid_spec",
+            Self::WriteError(WriteError::SpecVersionMismatch) => "spec_version_mismatch",
+            Self::WriteError(WriteError::BatchAborted) => "batch_aborted",
+            Self::GroupNotFound => "group_not_found",
+            Self::Unauthorized => "unauthorized",
+            Self::MissingIfMatch => "missing_if_match",
+            Self::BatchTooLarge => "batch_too_large",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::InvalidRequest { .. } => "invalid_request",
+        }
+    }
+
+    const fn status_code(&self) -> StatusCode {
+        match self {
+            Self::WriteError(WriteError::NameConflict) => StatusCode::CONFLICT,
+            Self::WriteError(WriteError::ReservedName) => StatusCode::UNPROCESSABLE_ENTITY,
             Self::ServiceNotFoundError | Self::WriteError(WriteError::NotFound) => {
-                (StatusCode::NOT_FOUND, ServiceNotFoundError.to_string())
+                StatusCode::NOT_FOUND
             }
+            Self::WriteError(WriteError::InvalidSpec(_)) => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::WriteError(WriteError::SpecVersionMismatch) => StatusCode::PRECONDITION_FAILED,
+            Self::WriteError(WriteError::BatchAborted) => StatusCode::FAILED_DEPENDENCY,
+            Self::GroupNotFound => StatusCode::NOT_FOUND,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
+            Self::MissingIfMatch => StatusCode::PRECONDITION_REQUIRED,
+            Self::BatchTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::InvalidRequest { status, .. } => *status,
         }
-        .into_response()
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (message, detail) = match &self {
+            Self::WriteError(WriteError::InvalidSpec(e)) => {
+                ("Invalid spec".to_string(), Some(e.to_string()))
+            }
+            Self::InvalidRequest { detail, .. } => (self.to_string(), Some(detail.clone())),
+            _ => (self.to_string(), None),
+        };
+        let body = ApiErrorBody {
+            code: self.code().to_string(),
+            message,
+            detail,
+        };
+        let mut response = (self.status_code(), axum::Json(body)).into_response();
+        if let Self::RateLimited { retry_after } = self {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
+
+/// Like `axum::Json`, but rejecting requests with an `ApiError`.
+pub struct Json<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequest<S> for Json<T>
+where
+    axum::Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::Json(value) = axum::Json::from_request(req, state).await?;
+        Ok(Self(value))
+    }
+}
+
+impl<T> IntoResponse for Json<T>
+where
+    axum::Json<T>: IntoResponse,
+{
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+/// Like `axum::extract::Query`, but rejecting requests with an `ApiError`.
+pub struct Query<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Query<T>
+where
+    axum::extract::Query<T>: FromRequestParts<S, Rejection = QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Query(value) =
+            axum::extract::Query::from_request_parts(parts, state).await?;
+        Ok(Self(value))
+    }
+}
+
+/// Like `axum::extract::Path`, but rejecting requests with an `ApiError`.
+pub struct Path<T>(pub T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Path<T>
+where
+    axum::extract::Path<T>: FromRequestParts<S, Rejection = PathRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let axum::extract::Path(value) =
+            axum::extract::Path::from_request_parts(parts, state).await?;
+        Ok(Self(value))
     }
 }
@@ -0,0 +1,49 @@
+use crate::api_util::ApiError;
+use axum::{
+    extract::{Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Which requests need the pre-shared API key.
+#[derive(Clone)]
+pub struct AuthConfig {
+    pub api_key: Arc<str>,
+    pub require_auth_for_reads: bool,
+}
+
+/// Middleware rejecting requests that need authentication but don't carry an
+/// `Authorization: Bearer <key>` header with the right key.
+///
+/// # Errors
+///
+/// If the request needs authentication and the key is missing or wrong.
+pub async fn require_api_key(
+    State(config): State<AuthConfig>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD);
+    if is_read && !config.require_auth_for_reads {
+        return Ok(next.run(request).await);
+    }
+
+    let key = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match key {
+        Some(key) if constant_time_eq(key.as_bytes(), config.api_key.as_bytes()) => {
+            Ok(next.run(request).await)
+        }
+        _ => Err(ApiError::Unauthorized),
+    }
+}
+
+/// Compare two byte strings without leaking where they differ through timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -1,49 +1,627 @@
 mod api_util;
+mod auth;
+mod notifier;
+mod persistence;
+mod rate_limit;
 mod state_actor;
+mod telemetry;
+mod ws;
 
-use api_util::ApiError;
+use api_util::{ApiError, Json, Path, Query};
+use auth::AuthConfig;
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    routing::{get, put},
-    Json, Router,
+    body::Body,
+    extract::State,
+    http::{header, HeaderMap, HeaderName, HeaderValue, Method, StatusCode},
+    middleware,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Response,
+    },
+    routing::{get, post, put},
+    Extension, Router,
 };
-use chrono::{DateTime, Utc};
-use clap::Parser;
-use state_actor::StateActorHandle;
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::{Parser, ValueEnum};
+use lettre::message::Mailbox;
+use notifier::EmailConfig;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use rate_limit::RateLimiter;
+use serde::{Deserialize, Serialize};
+use state_actor::{StateActorHandle, WriteError};
 use std::collections::{BTreeMap, VecDeque};
-use swec::{ServiceAction, ServiceSpec, TimedStatus};
-use tokio::spawn;
-use tokio::sync::broadcast;
-use tracing::info;
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::Duration;
+use swec::{
+    filter::Predicate, ApiErrorBody, GroupHealth, GroupStatus, Incident, MaintenanceWindow,
+    ServiceAction, ServiceSpec, ServiceWithStatus, SpecHistory, Status, StatusBucket, StatusStats,
+    StatusSummary, TimedStatus,
+};
+use tokio::signal;
+use tokio_stream::{wrappers::errors::BroadcastStreamRecvError, Stream, StreamExt};
+use tower_http::{
+    cors::{AllowOrigin, CorsLayer},
+    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+};
+use tracing::{info, warn, Level};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 #[tokio::main]
 async fn main() {
-    // TODO: env config
-    tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+
+    match cli.log_format {
+        LogFormat::Text => tracing_subscriber::fmt().init(),
+        LogFormat::Json => tracing_subscriber::fmt().json().init(),
+    }
+
+    if let Some(address) = cli.metrics_addr.clone() {
+        let idle_timeout = cli
+            .metrics_idle_timeout
+            .map(|timeout| Duration::from_secs(timeout.get()));
+        tokio::spawn(telemetry::serve(address, telemetry::install(idle_timeout)));
+    }
 
-    // TODO: Load state
-    let state_actor_handle = StateActorHandle::new(BTreeMap::new(), 32);
+    let services = cli
+        .state_file
+        .as_ref()
+        .map_or_else(Default::default, |path| {
+            info!("Loading state from {}", path.display());
+            persistence::load(path).expect("Couldn't load state file")
+        });
+    let mut notifiers: Vec<_> = cli
+        .webhook_url
+        .clone()
+        .map(notifier::spawn_webhook)
+        .into_iter()
+        .collect();
+    if let (Some(host), Some(from), Some(to)) = (
+        cli.smtp_host.clone(),
+        cli.smtp_from.clone(),
+        cli.smtp_to.clone(),
+    ) {
+        notifiers.push(notifier::spawn_email(EmailConfig {
+            host,
+            port: cli.smtp_port,
+            credentials: cli.smtp_user.clone().zip(cli.smtp_password.clone()),
+            from,
+            to,
+        }));
+    }
+    let state_actor_handle = StateActorHandle::new(
+        services,
+        cli.max_statuses.get(),
+        cli.max_spec_history,
+        notifiers,
+        cli.broadcast_capacity.get(),
+    );
 
-    let app = Router::new()
-        .route("/:name", put(put_action))
-        .route("/:name/statuses", get(get_statuses))
+    // Background tasks using the state actor, aborted before it is shut down.
+    let mut background_tasks = Vec::new();
+
+    if let (Some(path), Some(interval)) = (cli.state_file.clone(), cli.save_interval) {
+        background_tasks.push(tokio::spawn(save_periodically(
+            state_actor_handle.clone(),
+            path,
+            Duration::from_secs(interval.get()),
+        )));
+    }
+
+    if let Some(stale_after) = cli.stale_after {
+        background_tasks.push(tokio::spawn(watch_stale_services(
+            state_actor_handle.clone(),
+            Duration::from_secs(stale_after),
+        )));
+    }
+
+    if let Some(retention_days) = cli.retention_days {
+        background_tasks.push(tokio::spawn(trim_statuses_periodically(
+            state_actor_handle.clone(),
+            chrono::Duration::days(retention_days.into()),
+        )));
+    }
+
+    let mut app = Router::new()
+        .route("/", get(list_services))
+        // Other methods on these routes would be service writes, so respond as such.
+        .route("/health", get(get_health).fallback(reserved_name))
+        .route("/openapi.json", get(get_openapi).fallback(reserved_name))
+        .route("/docs", get(get_docs).fallback(reserved_name))
+        .route("/docs/swagger-ui.css", get(get_docs_css))
+        .route("/docs/swagger-ui-bundle.js", get(get_docs_js))
+        .route(
+            "/services",
+            get(list_services_with_status).fallback(reserved_name),
+        )
+        .route("/services/bulk", post(bulk_create_services))
+        .route("/services/changes", get(get_changes))
+        .route("/groups", get(list_groups).fallback(reserved_name))
+        .route("/groups/:group/status", get(get_group_health))
+        .route(
+            "/:name",
+            put(put_action).delete(delete_service).head({
+                let max_age = chrono::Duration::seconds(cli.liveness_max_age.into());
+                move |state, path| head_service(state, path, max_age)
+            }),
+        )
+        .route("/:name/statuses", get(get_statuses).post(post_statuses))
+        .route("/:name/statuses/export.csv", get(export_statuses_csv))
+        .route("/:name/statuses/downsample", get(get_downsampled_statuses))
+        .route("/:name/statuses/stats", get(get_stats))
+        .route("/:name/statuses/compact", post(compact_statuses))
         .route("/:name/status", get(get_status_at))
-        .route("/:name/spec", get(get_spec))
-        .with_state(state_actor_handle);
+        .route("/:name/status/latest", get(get_latest_status))
+        .route("/:name/spec", get(get_spec).put(put_spec))
+        .route("/:name/spec/history", get(get_spec_history))
+        .route("/:name/summary", get(get_summary))
+        .route("/:name/incidents", get(get_incidents))
+        .route("/:name/maintenance", put(put_maintenance))
+        .route("/:name/events", get(get_events))
+        .route("/:name/statuses/events", get(get_status_events))
+        .route("/:name/ws", get(ws::get_ws))
+        .with_state(state_actor_handle.clone());
 
-    let cli = Cli::parse();
+    if let Some(writes_per_second) = cli.max_writes_per_service_per_second {
+        app = app.layer(middleware::from_fn_with_state(
+            RateLimiter::new(writes_per_second.get()),
+            rate_limit::limit_writes,
+        ));
+    }
+    if let Some(api_key) = cli.api_key {
+        let auth_config = AuthConfig {
+            api_key: api_key.into(),
+            require_auth_for_reads: cli.require_auth_for_reads,
+        };
+        app = app.layer(middleware::from_fn_with_state(
+            auth_config,
+            auth::require_api_key,
+        ));
+    }
+    // Added last so that they also wrap error responses, including the ones for unknown routes.
+    let app = app.layer(cors_layer(&cli.cors_origin)).layer(
+        TraceLayer::new_for_http()
+            .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+            .on_response(DefaultOnResponse::new().level(Level::INFO)),
+    );
 
     info!("Binding to {}", cli.address);
     let listener = tokio::net::TcpListener::bind(cli.address)
         .await
         .expect("Couldn't create TCP listener");
-    info!("Starting API server");
-    axum::serve(listener, app)
+    let shutdown = {
+        let state_actor_handle = state_actor_handle.clone();
+        async move {
+            shutdown_signal().await;
+            // Event streams never end by themselves, and would keep the connections open.
+            state_actor_handle.close_streams();
+        }
+    };
+    if let (Some(cert), Some(key)) = (cli.tls_cert, cli.tls_key) {
+        let config = RustlsConfig::from_pem_file(cert, key)
+            .await
+            .expect("Couldn't load TLS certificate or key");
+        let handle = axum_server::Handle::new();
+        tokio::spawn({
+            let handle = handle.clone();
+            async move {
+                shutdown.await;
+                handle.graceful_shutdown(Some(SHUTDOWN_GRACE_PERIOD));
+            }
+        });
+        info!("Starting API server with TLS");
+        axum_server::from_tcp_rustls(
+            listener.into_std().expect("Couldn't convert TCP listener"),
+            config,
+        )
+        .handle(handle)
+        .serve(app.into_make_service())
         .await
         .expect("Couldn't start API server");
+    } else {
+        info!("Starting API server");
+        axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .expect("Couldn't start API server");
+    }
+
+    // Wait for the tasks to be aborted, so that a periodic save can't overwrite the final one.
+    for task in background_tasks {
+        task.abort();
+        let _ = task.await;
+    }
+    let services = state_actor_handle.shutdown().await;
+    if let Some(path) = cli.state_file {
+        info!("Saving state to {}", path.display());
+        persistence::save(&path, &services).expect("Couldn't save state file");
+    }
+}
+
+/// Save the state to `path` every `interval`, so that less is lost if the distributor crashes.
+async fn save_periodically(
+    state_actor_handle: StateActorHandle,
+    path: PathBuf,
+    interval: Duration,
+) {
+    let mut interval = tokio::time::interval(interval);
+    // The first tick completes immediately, and the state was just loaded.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let services = state_actor_handle.snapshot().await;
+        if let Err(e) = persistence::save(&path, &services) {
+            warn!("Couldn't save state to {}: {e}", path.display());
+        }
+    }
+}
+
+/// How often statuses older than the retention period are dropped.
+const RETENTION_TRIM_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Regularly drop the statuses older than `retention`.
+async fn trim_statuses_periodically(
+    state_actor_handle: StateActorHandle,
+    retention: chrono::Duration,
+) {
+    let mut interval = tokio::time::interval(RETENTION_TRIM_INTERVAL);
+    loop {
+        interval.tick().await;
+        // Nothing can be older than a retention period reaching before the earliest time.
+        if let Some(older_than) = Utc::now().checked_sub_signed(retention) {
+            state_actor_handle.trim_statuses(older_than).await;
+        }
+    }
+}
+
+/// Add a `Status::Unknown` to services that haven't received a status for `stale_after`, e.g.
+/// because their checker crashed. This also notifies of the transition if they were up.
+///
+/// Services are only marked once, until they receive a status again.
+async fn watch_stale_services(state_actor_handle: StateActorHandle, stale_after: Duration) {
+    let mut interval = tokio::time::interval(stale_after / 2);
+    let stale_after_chrono =
+        chrono::Duration::from_std(stale_after).unwrap_or(chrono::Duration::max_value());
+    let stale_status = Status::Unknown(format!("No status received in the last {stale_after:?}"));
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        // No status can have been received before the earliest time.
+        let Some(received_before) = now.checked_sub_signed(stale_after_chrono) else {
+            continue;
+        };
+        let actions: Vec<_> = state_actor_handle
+            .list_stale(received_before)
+            .await
+            .into_iter()
+            .filter(|(_, latest)| latest.inner != stale_status)
+            .map(|(name, _)| {
+                warn!("No status received for {name} in the last {stale_after:?}");
+                let status = TimedStatus {
+                    time: now,
+                    inner: stale_status.clone(),
+                };
+                (name, ServiceAction::AddStatus(status))
+            })
+            .collect();
+        if !actions.is_empty() {
+            state_actor_handle.batch_write(actions).await;
+        }
+    }
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_services,
+        list_services_with_status,
+        bulk_create_services,
+        get_changes,
+        list_groups,
+        get_group_health,
+        get_health,
+        put_action,
+        delete_service,
+        head_service,
+        get_statuses,
+        export_statuses_csv,
+        get_downsampled_statuses,
+        get_stats,
+        post_statuses,
+        compact_statuses,
+        get_status_at,
+        get_latest_status,
+        get_spec,
+        put_spec,
+        get_spec_history,
+        get_summary,
+        get_incidents,
+        put_maintenance,
+        get_events,
+        get_status_events,
+        ws::get_ws,
+    ),
+    components(schemas(
+        ApiErrorBody,
+        BatchEntryResult,
+        Compaction,
+        GroupHealth,
+        GroupStatus,
+        Health,
+        Incident,
+        MaintenanceWindow,
+        ServiceAction,
+        ServiceSpec,
+        ServiceWithStatus,
+        Status,
+        StatusBucket,
+        StatusStats,
+        StatusSummary,
+        TimedStatus,
+    ))
+)]
+struct ApiDoc;
+
+/// Get the OpenAPI description of the API.
+async fn get_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Get a Swagger UI page for the OpenAPI description of the API.
+async fn get_docs() -> Html<&'static str> {
+    Html(include_str!("docs.html"))
+}
+
+/// Get the stylesheet of Swagger UI 5.17.14, which is vendored so that the docs page doesn't run
+/// third-party code from a CDN next to the API.
+async fn get_docs_css() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/css")],
+        include_str!("swagger-ui/swagger-ui.css"),
+    )
+}
+
+/// Get the script of Swagger UI, vendored like its stylesheet.
+async fn get_docs_js() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/javascript")],
+        include_str!("swagger-ui/swagger-ui-bundle.js"),
+    )
+}
+
+/// Build the CORS layer allowing browser-based dashboards on `origins` to use the API.
+///
+/// An origin of `*` allows any origin.
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|o| o == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(
+            origins
+                .iter()
+                .map(|o| HeaderValue::from_str(o).expect("Invalid CORS origin")),
+        )
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::PUT, Method::DELETE, Method::POST])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::IF_MATCH,
+        ])
+        .expose_headers([header::ETAG, header::LINK, header::RETRY_AFTER])
+}
+
+/// How long connections are given to finish on shutdown when serving over TLS.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Resolve when the process receives either SIGINT (Ctrl+C) or SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("Couldn't install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("Couldn't install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+
+    info!("Shutting down");
+}
+
+#[derive(Deserialize)]
+struct ServiceFilter {
+    tag: Option<String>,
+    /// Labels the services must have, as `label.<key>=<value>`.
+    #[serde(flatten)]
+    others: BTreeMap<String, String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    params(
+        ("tag" = Option<String>, Query, description = "Only list services with this tag"),
+        ("label.<key>" = Option<String>, Query, description = "Only list services with this label"),
+    ),
+    responses((status = 200, description = "Names of the services", body = Vec<String>))
+)]
+async fn list_services(
+    State(state_actor_handle): State<StateActorHandle>,
+    Query(filter): Query<ServiceFilter>,
+) -> (StatusCode, Json<Vec<String>>) {
+    let labels = filter
+        .others
+        .into_iter()
+        .filter_map(|(key, value)| Some((key.strip_prefix("label.")?.to_string(), value)))
+        .collect();
+    let names = state_actor_handle.list_services(filter.tag, labels).await;
+    (StatusCode::OK, Json(names))
+}
+
+/// Get every service along with its latest status, to avoid one request per service.
+#[utoipa::path(
+    get,
+    path = "/services",
+    responses((status = 200, body = BTreeMap<String, ServiceWithStatus>))
+)]
+async fn list_services_with_status(
+    State(state_actor_handle): State<StateActorHandle>,
+) -> (StatusCode, Json<BTreeMap<String, ServiceWithStatus>>) {
+    let services = state_actor_handle.list_services_with_status().await;
+    (StatusCode::OK, Json(services))
+}
+
+/// Create all the services, or none of them if any can't be created.
+///
+/// Responds with 207 Multi-Status and the result for each service.
+#[utoipa::path(
+    post,
+    path = "/services/bulk",
+    request_body = BTreeMap<String, ServiceSpec>,
+    responses((
+        status = 207,
+        description = "Result for each service",
+        body = BTreeMap<String, BatchEntryResult>,
+    ))
+)]
+async fn bulk_create_services(
+    State(state_actor_handle): State<StateActorHandle>,
+    Json(specs): Json<BTreeMap<String, ServiceSpec>>,
+) -> Result<(StatusCode, Json<BTreeMap<String, BatchEntryResult>>), ApiError> {
+    if specs.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::BatchTooLarge);
+    }
+    let results = state_actor_handle
+        .bulk_create(specs)
+        .await
+        .into_iter()
+        .map(|(name, r)| (name, r.into()))
+        .collect();
+    Ok((StatusCode::MULTI_STATUS, Json(results)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Changes {
+    since: DateTime<Utc>,
+}
+
+/// Get the statuses received since `since`, whatever their own time, of each service that
+/// received any, to sync incrementally instead of fetching every service.
+#[utoipa::path(
+    get,
+    path = "/services/changes",
+    params(Changes),
+    responses((
+        status = 200,
+        description = "Statuses received by each service, newest first",
+        body = BTreeMap<String, Vec<TimedStatus>>,
+    ))
+)]
+async fn get_changes(
+    State(state_actor_handle): State<StateActorHandle>,
+    Query(changes): Query<Changes>,
+) -> (StatusCode, Json<BTreeMap<String, Vec<TimedStatus>>>) {
+    let changes = state_actor_handle.diff_snapshot(changes.since).await;
+    (StatusCode::OK, Json(changes.into_iter().collect()))
+}
+
+/// Get the aggregated status of each group of services.
+#[utoipa::path(
+    get,
+    path = "/groups",
+    responses((status = 200, body = BTreeMap<String, GroupStatus>))
+)]
+async fn list_groups(
+    State(state_actor_handle): State<StateActorHandle>,
+) -> (StatusCode, Json<BTreeMap<String, GroupStatus>>) {
+    let groups = state_actor_handle.list_groups().await;
+    (StatusCode::OK, Json(groups))
+}
+
+/// Get whether each service in the group is up.
+#[utoipa::path(
+    get,
+    path = "/groups/{group}/status",
+    params(("group" = String, Path, description = "Name of the group")),
+    responses(
+        (status = 200, body = GroupHealth),
+        (status = 404, description = "No service is in the group", body = ApiErrorBody),
+    )
+)]
+async fn get_group_health(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(group): Path<String>,
+) -> Result<(StatusCode, Json<GroupHealth>), ApiError> {
+    let health = state_actor_handle
+        .get_group_health(group)
+        .await
+        .ok_or(ApiError::GroupNotFound)?;
+    Ok((StatusCode::OK, Json(health)))
 }
 
+/// Reject writes to a service named like one of the distributor's own routes.
+async fn reserved_name() -> ApiError {
+    WriteError::ReservedName.into()
+}
+
+#[derive(Serialize, ToSchema)]
+struct Health {
+    status: &'static str,
+    services_count: usize,
+}
+
+/// Check that the distributor is alive and its state actor responsive.
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses((status = 200, body = Health))
+)]
+async fn get_health(
+    State(state_actor_handle): State<StateActorHandle>,
+) -> (StatusCode, Json<Health>) {
+    let services_count = state_actor_handle
+        .list_services(None, BTreeMap::new())
+        .await
+        .len();
+    (
+        StatusCode::OK,
+        Json(Health {
+            status: "ok",
+            services_count,
+        }),
+    )
+}
+
+#[utoipa::path(
+    put,
+    path = "/{name}",
+    params(("name" = String, Path, description = "Name of the service")),
+    request_body = ServiceAction,
+    responses(
+        (status = 204, description = "Action executed"),
+        (status = 404, body = ApiErrorBody),
+        (status = 409, body = ApiErrorBody),
+        (status = 422, body = ApiErrorBody),
+    )
+)]
 async fn put_action(
     State(state_actor_handle): State<StateActorHandle>,
     Path(name): Path<String>,
@@ -53,14 +631,385 @@ async fn put_action(
     Ok((StatusCode::NO_CONTENT, "Action executed".to_string()))
 }
 
+#[utoipa::path(
+    delete,
+    path = "/{name}",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses((status = 204, description = "Service deleted"), (status = 404, body = ApiErrorBody))
+)]
+async fn delete_service(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, String), ApiError> {
+    state_actor_handle
+        .write(name, ServiceAction::DeleteService)
+        .await?;
+    Ok((StatusCode::NO_CONTENT, "Service deleted".to_string()))
+}
+
+/// Check whether the service is up, for load balancer health checks: 200 if its latest status is
+/// up and at most `max_age` old, 503 otherwise.
+#[utoipa::path(
+    head,
+    path = "/{name}",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses(
+        (status = 200, description = "The service is up"),
+        (status = 503, description = "The service is down, or its latest status is too old"),
+        (status = 404, description = "The service doesn't exist"),
+    )
+)]
+async fn head_service(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    max_age: chrono::Duration,
+) -> Result<StatusCode, ApiError> {
+    let status = state_actor_handle.get_latest_status(name).await?;
+    let is_live = status.is_some_and(|s| s.inner.is_up() && Utc::now() - s.time <= max_age);
+    Ok(if is_live {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    })
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Pagination {
+    #[serde(default)]
+    offset: usize,
+    #[param(value_type = Option<usize>, minimum = 1)]
+    limit: Option<NonZeroUsize>,
+}
+
+#[derive(Deserialize, Clone, Copy, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct TimeRange {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl From<TimeRange> for RangeInclusive<DateTime<Utc>> {
+    fn from(value: TimeRange) -> Self {
+        value.since.unwrap_or(DateTime::<Utc>::MIN_UTC)
+            ..=value.until.unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+impl From<TimeRange> for Predicate {
+    fn from(value: TimeRange) -> Self {
+        Self::After(value.since.unwrap_or(DateTime::<Utc>::MIN_UTC)).and(Self::Before(
+            value.until.unwrap_or(DateTime::<Utc>::MAX_UTC),
+        ))
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct StatusFilter {
+    /// Only keep the up statuses if true, or the down ones if false
+    up: Option<bool>,
+    /// Only keep the down or unknown statuses whose reason contains this string
+    message: Option<String>,
+}
+
+impl StatusFilter {
+    /// Restrict `pred` to the statuses kept by the filter.
+    fn restrict(&self, mut pred: Predicate) -> Predicate {
+        match self.up {
+            Some(true) => pred = pred.and(Predicate::IsUp),
+            Some(false) => pred = pred.and(Predicate::IsDown),
+            None => {}
+        }
+        if let Some(message) = &self.message {
+            pred = pred.and(Predicate::MessageContains(message.clone()));
+        }
+        pred
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{name}/statuses",
+    params(
+        ("name" = String, Path, description = "Name of the service"),
+        TimeRange,
+        StatusFilter,
+        Pagination,
+    ),
+    responses(
+        (
+            status = 200,
+            description = "Statuses, newest first",
+            body = Vec<TimedStatus>,
+            headers(("Link" = String, description = "Link to the next page, if any")),
+        ),
+        (status = 404, body = ApiErrorBody),
+    )
+)]
 async fn get_statuses(
     State(state_actor_handle): State<StateActorHandle>,
     Path(name): Path<String>,
-) -> Result<(StatusCode, Json<VecDeque<TimedStatus>>), ApiError> {
-    let statuses = state_actor_handle.get_statuses(name).await?;
-    Ok((StatusCode::OK, Json(statuses)))
+    Query(time_range): Query<TimeRange>,
+    Query(status_filter): Query<StatusFilter>,
+    Query(pagination): Query<Pagination>,
+) -> Result<(StatusCode, HeaderMap, Json<VecDeque<TimedStatus>>), ApiError> {
+    let statuses = state_actor_handle
+        .get_statuses(
+            name,
+            status_filter.restrict(time_range.into()),
+            pagination.offset,
+            pagination.limit.map(NonZeroUsize::get),
+        )
+        .await?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(limit) = pagination.limit {
+        // A full page means there may be more statuses after it.
+        if statuses.len() == limit.get() {
+            let mut next = format!("<?offset={}&limit={limit}", pagination.offset + limit.get());
+            for (key, time) in [("since", time_range.since), ("until", time_range.until)] {
+                if let Some(time) = time {
+                    let time = time.to_rfc3339_opts(SecondsFormat::AutoSi, true);
+                    next.push_str(&format!("&{key}={time}"));
+                }
+            }
+            if let Some(up) = status_filter.up {
+                next.push_str(&format!("&up={up}"));
+            }
+            if let Some(message) = &status_filter.message {
+                let message = utf8_percent_encode(message, NON_ALPHANUMERIC);
+                next.push_str(&format!("&message={message}"));
+            }
+            next.push_str(">; rel=\"next\"");
+            headers.insert(
+                header::LINK,
+                HeaderValue::from_str(&next).expect("Link header should be valid"),
+            );
+        }
+    }
+
+    Ok((StatusCode::OK, headers, Json(statuses)))
+}
+
+/// Export the statuses as CSV, oldest first, with `timestamp,is_up,message` columns.
+#[utoipa::path(
+    get,
+    path = "/{name}/statuses/export.csv",
+    params(("name" = String, Path, description = "Name of the service"), TimeRange),
+    responses(
+        (status = 200, description = "Statuses, oldest first", body = String, content_type = "text/csv"),
+        (status = 404, body = ApiErrorBody),
+    )
+)]
+async fn export_statuses_csv(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    Query(time_range): Query<TimeRange>,
+) -> Result<Response, ApiError> {
+    // Fail before the response starts if the service doesn't exist.
+    state_actor_handle.get_spec(name.clone()).await?;
+    let pred: Predicate = time_range.into();
+    // Fetch the statuses a page at a time rather than copying all of them at once. Statuses
+    // added or dropped during the export may be missed.
+    let pages = futures_util::stream::unfold(Some(0), move |offset| {
+        let (state_actor_handle, name, pred) =
+            (state_actor_handle.clone(), name.clone(), pred.clone());
+        async move {
+            let offset = offset?;
+            // The service may have been deleted since, end the export early then.
+            let page = state_actor_handle
+                .get_oldest_statuses(name, pred, offset, Some(EXPORT_PAGE_SIZE))
+                .await
+                .ok()?;
+            let next_offset = (page.len() == EXPORT_PAGE_SIZE).then_some(offset + page.len());
+            Some((tokio_stream::iter(page), next_offset))
+        }
+    });
+    let rows = futures_util::StreamExt::flatten(pages).map(|s| {
+        csv_row([
+            s.time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            s.inner.is_up().to_string(),
+            s.inner.to_string(),
+        ])
+    });
+    let header = csv_row(["timestamp", "is_up", "message"].map(String::from));
+    let body = Body::from_stream(tokio_stream::once(header).chain(rows));
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"statuses.csv\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Number of statuses fetched from the state actor at a time when exporting them.
+const EXPORT_PAGE_SIZE: usize = 1000;
+
+/// Encode a single CSV row, including the trailing newline.
+fn csv_row(record: [String; 3]) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(record)?;
+    Ok(writer
+        .into_inner()
+        .expect("Writing to a Vec shouldn't fail"))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct Downsampling {
+    bucket_seconds: NonZeroU32,
+}
+
+/// Get the number of up and down statuses in each `bucket_seconds`-long period, oldest first.
+#[utoipa::path(
+    get,
+    path = "/{name}/statuses/downsample",
+    params(("name" = String, Path, description = "Name of the service"), TimeRange, Downsampling),
+    responses((status = 200, body = Vec<StatusBucket>), (status = 404, body = ApiErrorBody))
+)]
+async fn get_downsampled_statuses(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    Query(time_range): Query<TimeRange>,
+    Query(downsampling): Query<Downsampling>,
+) -> Result<(StatusCode, Json<Vec<StatusBucket>>), ApiError> {
+    let statuses = state_actor_handle
+        .get_statuses(name, time_range.into(), 0, None)
+        .await?;
+    let bucket = chrono::Duration::seconds(downsampling.bucket_seconds.get().into());
+    Ok((
+        StatusCode::OK,
+        Json(StatusBucket::from_statuses(&statuses, bucket)),
+    ))
+}
+
+/// Get the check counts and outages of the service within the time range, outside of its
+/// maintenance window.
+#[utoipa::path(
+    get,
+    path = "/{name}/statuses/stats",
+    params(("name" = String, Path, description = "Name of the service"), TimeRange),
+    responses((status = 200, body = StatusStats), (status = 404, body = ApiErrorBody))
+)]
+async fn get_stats(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    Query(time_range): Query<TimeRange>,
+) -> Result<(StatusCode, Json<StatusStats>), ApiError> {
+    let stats = state_actor_handle
+        .get_stats(name, time_range.into())
+        .await?;
+    Ok((StatusCode::OK, Json(stats)))
+}
+
+/// Maximum number of statuses accepted in a single `POST /:name/statuses` request.
+const MAX_BATCH_SIZE: usize = 1000;
+
+#[derive(Serialize, ToSchema)]
+struct BatchEntryResult {
+    success: bool,
+    error: Option<String>,
+}
+
+impl From<Result<(), WriteError>> for BatchEntryResult {
+    fn from(value: Result<(), WriteError>) -> Self {
+        Self {
+            success: value.is_ok(),
+            error: value.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+/// Add many statuses at once, e.g. to import them from another system.
+///
+/// The statuses are inserted at their place in time, and no notifications are sent for the
+/// transitions between them. Responds with 207 Multi-Status and the result of each entry, in the
+/// same order. Entries that aren't valid statuses, e.g. with a malformed time, are reported there
+/// too.
+#[utoipa::path(
+    post,
+    path = "/{name}/statuses",
+    params(("name" = String, Path, description = "Name of the service")),
+    request_body = Vec<TimedStatus>,
+    responses(
+        (status = 207, description = "Result of each entry", body = Vec<BatchEntryResult>),
+        (status = 413, body = ApiErrorBody),
+    )
+)]
+async fn post_statuses(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    rate_limiter: Option<Extension<RateLimiter>>,
+    Json(entries): Json<Vec<serde_json::Value>>,
+) -> Result<(StatusCode, Json<Vec<BatchEntryResult>>), ApiError> {
+    if entries.len() > MAX_BATCH_SIZE {
+        return Err(ApiError::BatchTooLarge);
+    }
+    // The rate limiter only charged the request itself, but each entry is a write.
+    if let Some(Extension(rate_limiter)) = rate_limiter {
+        rate_limiter.charge(&name, entries.len().saturating_sub(1));
+    }
+    let parsed: Vec<_> = entries
+        .into_iter()
+        .map(serde_json::from_value::<TimedStatus>)
+        .collect();
+    let statuses = parsed
+        .iter()
+        .filter_map(|entry| entry.as_ref().ok())
+        .cloned()
+        .collect();
+    let import_result = state_actor_handle.import_statuses(name, statuses).await;
+    let results = parsed
+        .into_iter()
+        .map(|entry| match entry {
+            Ok(_) => import_result.into(),
+            Err(e) => BatchEntryResult {
+                success: false,
+                error: Some(format!("Invalid status: {e}")),
+            },
+        })
+        .collect();
+    Ok((StatusCode::MULTI_STATUS, Json(results)))
 }
 
+#[derive(Serialize, ToSchema)]
+struct Compaction {
+    old_len: usize,
+    new_len: usize,
+}
+
+/// Keep only the statuses where the service goes up or down.
+#[utoipa::path(
+    post,
+    path = "/{name}/statuses/compact",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses(
+        (status = 200, description = "Number of statuses before and after", body = Compaction),
+        (status = 404, body = ApiErrorBody),
+    )
+)]
+async fn compact_statuses(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<Compaction>), ApiError> {
+    let (old_len, new_len) = state_actor_handle.compact_statuses(name).await?;
+    Ok((StatusCode::OK, Json(Compaction { old_len, new_len })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{name}/status",
+    params(("name" = String, Path, description = "Name of the service")),
+    request_body(content = String, description = "RFC 3339 time to get the closest status to"),
+    responses((status = 200, body = Option<TimedStatus>), (status = 404, body = ApiErrorBody))
+)]
 async fn get_status_at(
     State(state_actor_handle): State<StateActorHandle>,
     Path(name): Path<String>,
@@ -70,18 +1019,340 @@ async fn get_status_at(
     Ok((StatusCode::OK, Json(status)))
 }
 
+/// Get the most recent status, or 204 No Content if the service has no statuses yet.
+#[utoipa::path(
+    get,
+    path = "/{name}/status/latest",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses(
+        (status = 200, body = TimedStatus),
+        (status = 204, description = "The service has no statuses yet"),
+        (status = 404, body = ApiErrorBody),
+    )
+)]
+async fn get_latest_status(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+) -> Result<Response, ApiError> {
+    let status = state_actor_handle.get_latest_status(name).await?;
+    Ok(status.map_or_else(
+        || StatusCode::NO_CONTENT.into_response(),
+        |status| (StatusCode::OK, Json(status)).into_response(),
+    ))
+}
+
+/// Get the spec, with its version as the `ETag` header.
+#[utoipa::path(
+    get,
+    path = "/{name}/spec",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses(
+        (status = 200, body = ServiceSpec, headers(("ETag" = String, description = "Version of the spec"))),
+        (status = 404, body = ApiErrorBody),
+    )
+)]
 async fn get_spec(
     State(state_actor_handle): State<StateActorHandle>,
     Path(name): Path<String>,
-) -> Result<(StatusCode, Json<ServiceSpec>), ApiError> {
-    let spec = state_actor_handle.get_spec(name).await?;
-    Ok((StatusCode::OK, Json(spec)))
+) -> Result<(StatusCode, [(HeaderName, String); 1], Json<ServiceSpec>), ApiError> {
+    let (spec, version) = state_actor_handle.get_spec(name).await?;
+    Ok((
+        StatusCode::OK,
+        [(header::ETAG, format!("\"{version}\""))],
+        Json(spec),
+    ))
+}
+
+/// Update the spec, keeping the current value of the fields that aren't set in the new one. The
+/// `If-Match` header must contain the `ETag` of the spec being updated, or `*` to update it
+/// regardless of its version.
+#[utoipa::path(
+    put,
+    path = "/{name}/spec",
+    params(
+        ("name" = String, Path, description = "Name of the service"),
+        ("If-Match" = String, Header, description = "ETag of the spec being replaced, or `*`"),
+    ),
+    request_body = ServiceSpec,
+    responses(
+        (status = 204, description = "Spec updated"),
+        (status = 404, body = ApiErrorBody),
+        (status = 412, body = ApiErrorBody),
+        (status = 422, body = ApiErrorBody),
+        (status = 428, body = ApiErrorBody),
+    )
+)]
+async fn put_spec(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(spec): Json<ServiceSpec>,
+) -> Result<(StatusCode, String), ApiError> {
+    let if_match = headers
+        .get(header::IF_MATCH)
+        .ok_or(ApiError::MissingIfMatch)?
+        .to_str()
+        .unwrap_or_default();
+    let action = ServiceAction::UpdateSpec(spec);
+    if if_match == "*" {
+        state_actor_handle.write(name, action).await?;
+    } else {
+        // An ETag that isn't one of ours can't match the current version.
+        let version = if_match
+            .trim_matches('"')
+            .parse()
+            .map_err(|_| WriteError::SpecVersionMismatch)?;
+        state_actor_handle
+            .write_if_spec_version(name, action, version)
+            .await?;
+    }
+    Ok((StatusCode::NO_CONTENT, "Spec updated".to_string()))
+}
+
+/// Get the previous specs along with the time they were replaced, newest first.
+#[utoipa::path(
+    get,
+    path = "/{name}/spec/history",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses(
+        (
+            status = 200,
+            description = "Pairs of the time a spec was replaced and that spec, newest first",
+            body = Vec<Object>,
+        ),
+        (status = 404, body = ApiErrorBody),
+    )
+)]
+async fn get_spec_history(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<SpecHistory>), ApiError> {
+    let history = state_actor_handle.get_spec_history(name).await?;
+    Ok((StatusCode::OK, Json(history)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{name}/summary",
+    params(("name" = String, Path, description = "Name of the service"), TimeRange),
+    responses((status = 200, body = StatusSummary), (status = 404, body = ApiErrorBody))
+)]
+async fn get_summary(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    Query(time_range): Query<TimeRange>,
+) -> Result<(StatusCode, Json<StatusSummary>), ApiError> {
+    let statuses = state_actor_handle
+        .get_statuses(name, time_range.into(), 0, None)
+        .await?;
+    Ok((
+        StatusCode::OK,
+        Json(StatusSummary::from_statuses(&statuses)),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{name}/incidents",
+    params(("name" = String, Path, description = "Name of the service"), TimeRange),
+    responses((status = 200, body = Vec<Incident>), (status = 404, body = ApiErrorBody))
+)]
+async fn get_incidents(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    Query(time_range): Query<TimeRange>,
+) -> Result<(StatusCode, Json<Vec<Incident>>), ApiError> {
+    let (spec, _) = state_actor_handle.get_spec(name.clone()).await?;
+    let statuses = state_actor_handle
+        .get_statuses(name, time_range.into(), 0, None)
+        .await?;
+    // Statuses are returned newest first.
+    let incidents = Incident::from_statuses(
+        statuses
+            .iter()
+            .rev()
+            .filter(|s| !spec.is_in_maintenance(s.time)),
+    );
+    Ok((StatusCode::OK, Json(incidents)))
+}
+
+/// Create or replace the maintenance window of the service.
+#[utoipa::path(
+    put,
+    path = "/{name}/maintenance",
+    params(("name" = String, Path, description = "Name of the service")),
+    request_body = MaintenanceWindow,
+    responses(
+        (status = 204, description = "Maintenance window set"),
+        (status = 404, body = ApiErrorBody),
+        (status = 422, body = ApiErrorBody),
+    )
+)]
+async fn put_maintenance(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    Json(window): Json<MaintenanceWindow>,
+) -> Result<(StatusCode, String), ApiError> {
+    state_actor_handle
+        .write(name, ServiceAction::SetMaintenance(window))
+        .await?;
+    Ok((StatusCode::NO_CONTENT, "Maintenance window set".to_string()))
+}
+
+/// Stream the actions executed on the service as Server-Sent Events.
+#[utoipa::path(
+    get,
+    path = "/{name}/events",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses((
+        status = 200,
+        description = "Server-Sent Events stream of the actions executed on the service",
+        body = ServiceAction,
+        content_type = "text/event-stream",
+    ))
+)]
+async fn get_events(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let stream = state_actor_handle
+        .watch_actions()
+        .filter_map(move |msg| match msg {
+            Ok((service_name, action)) if service_name == name => {
+                Some(Event::default().json_data(action))
+            }
+            Ok(_) => None,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!("Events stream of {name} lagged behind, {skipped} actions were dropped");
+                None
+            }
+        });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Stream the statuses added to the service as Server-Sent Events.
+#[utoipa::path(
+    get,
+    path = "/{name}/statuses/events",
+    params(("name" = String, Path, description = "Name of the service")),
+    responses((
+        status = 200,
+        description = "Server-Sent Events stream of the statuses added to the service",
+        body = TimedStatus,
+        content_type = "text/event-stream",
+    ))
+)]
+async fn get_status_events(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let stream = state_actor_handle
+        .watch_service(name)
+        .map(|status| Event::default().json_data(status));
+    Sse::new(stream).keep_alive(KeepAlive::default())
 }
 
 #[derive(Parser)]
 #[command(version, about)]
 struct Cli {
     /// Listening address for private API
-    #[arg(short, long, default_value = "0.0.0.0:8080")]
+    #[arg(short, long, env = "SWEC_ADDRESS", default_value = "0.0.0.0:8080")]
     address: String,
+    /// PEM file of the TLS certificate chain to serve the API over HTTPS with
+    #[arg(long, env = "SWEC_TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+    /// PEM file of the private key of the TLS certificate
+    #[arg(long, env = "SWEC_TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+    /// Maximum number of statuses kept per service unless set in its spec, older ones are dropped
+    #[arg(long, env = "SWEC_MAX_STATUSES", default_value = "32")]
+    max_statuses: NonZeroUsize,
+    /// Drop statuses older than this many days, in addition to the maximum number of statuses
+    #[arg(
+        long,
+        env = "SWEC_RETENTION_DAYS",
+        value_parser = clap::value_parser!(u32).range(1..=36_500)
+    )]
+    retention_days: Option<u32>,
+    /// Maximum number of previous specs kept per service
+    #[arg(long, env = "SWEC_MAX_SPEC_HISTORY", default_value_t = 10)]
+    max_spec_history: usize,
+    /// Number of actions kept for event stream subscribers that lag behind
+    #[arg(long, env = "SWEC_BROADCAST_CAPACITY", default_value = "32")]
+    broadcast_capacity: NonZeroUsize,
+    /// Path to the file the state is loaded from on startup and saved to on shutdown
+    #[arg(long, env = "SWEC_STATE_FILE")]
+    state_file: Option<PathBuf>,
+    /// Also save the state to the state file every this many seconds
+    #[arg(long, env = "SWEC_SAVE_INTERVAL", requires = "state_file")]
+    save_interval: Option<NonZeroU64>,
+    /// URL to POST a JSON notification to when a service goes up or down
+    #[arg(long, env = "SWEC_WEBHOOK_URL")]
+    webhook_url: Option<String>,
+    /// SMTP server to send an email through when a service goes up or down
+    #[arg(long, env = "SWEC_SMTP_HOST", requires_all = ["smtp_from", "smtp_to"])]
+    smtp_host: Option<String>,
+    /// Port of the SMTP server, which must support STARTTLS
+    #[arg(long, env = "SWEC_SMTP_PORT", default_value_t = 587)]
+    smtp_port: u16,
+    /// User to authenticate to the SMTP server as
+    #[arg(long, env = "SWEC_SMTP_USER", requires = "smtp_password")]
+    smtp_user: Option<String>,
+    /// Password to authenticate to the SMTP server with
+    #[arg(long, env = "SWEC_SMTP_PASSWORD", hide_env_values = true)]
+    smtp_password: Option<String>,
+    /// Sender of the emails, e.g. `swec <swec@example.com>`
+    #[arg(long, env = "SWEC_SMTP_FROM")]
+    smtp_from: Option<Mailbox>,
+    /// Recipient of the emails
+    #[arg(long, env = "SWEC_SMTP_TO")]
+    smtp_to: Option<Mailbox>,
+    /// Comma-separated origins allowed to call the API from a browser, or `*` for any
+    #[arg(
+        long,
+        env = "SWEC_CORS_ORIGIN",
+        value_delimiter = ',',
+        default_value = "*"
+    )]
+    cors_origin: Vec<String>,
+    /// API key required as `Authorization: Bearer <key>` on requests modifying the state
+    #[arg(long, env = "SWEC_API_KEY", hide_env_values = true)]
+    api_key: Option<String>,
+    /// Also require the API key on read-only requests
+    #[arg(long, env = "SWEC_REQUIRE_AUTH_FOR_READS", requires = "api_key")]
+    require_auth_for_reads: bool,
+    /// Maximum number of writes per second to a single service, unlimited if not set
+    #[arg(long, env = "SWEC_MAX_WRITES_PER_SERVICE_PER_SECOND")]
+    max_writes_per_service_per_second: Option<NonZeroU32>,
+    /// Maximum age in seconds of an up status for `HEAD /<name>` to consider the service up
+    #[arg(long, env = "SWEC_LIVENESS_MAX_AGE", default_value_t = 300)]
+    liveness_max_age: u32,
+    /// Mark services as unknown when they haven't received a status for this many seconds, at
+    /// most a year
+    #[arg(
+        long,
+        env = "SWEC_STALE_AFTER",
+        value_parser = clap::value_parser!(u64).range(1..=365 * 24 * 60 * 60)
+    )]
+    stale_after: Option<u64>,
+    /// Listening address for the Prometheus metrics endpoint, disabled if not set
+    #[arg(long, env = "SWEC_METRICS_ADDR")]
+    metrics_addr: Option<String>,
+    /// Stop exporting the metrics of a service once it hasn't received a status for this many
+    /// seconds, e.g. because it was deleted. Should be longer than the longest interval between
+    /// the statuses of a service, which would otherwise have gaps in its metrics. If not set, the
+    /// metrics of deleted services are exported until restarting
+    #[arg(long, env = "SWEC_METRICS_IDLE_TIMEOUT")]
+    metrics_idle_timeout: Option<NonZeroU64>,
+    /// Format of the logs
+    #[arg(long, env = "SWEC_LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LogFormat {
+    /// Human-readable logs
+    Text,
+    /// One JSON object per line, for log aggregators
+    Json,
 }
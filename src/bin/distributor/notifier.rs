@@ -0,0 +1,125 @@
+use chrono::{DateTime, Utc};
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::Serialize;
+use std::time::Duration;
+use swec::Status;
+use tokio::sync::mpsc;
+use tracing::warn;
+
+/// A service going from up to down or the other way around.
+#[derive(Serialize, Debug, Clone)]
+pub struct Transition {
+    pub service: String,
+    pub previous: Status,
+    pub current: Status,
+    pub time: DateTime<Utc>,
+}
+
+impl Transition {
+    /// Create a `Transition` if going from `previous` to `current` changes whether the service is
+    /// up.
+    pub fn detect(
+        service: &str,
+        previous: &Status,
+        current: &Status,
+        time: DateTime<Utc>,
+    ) -> Option<Self> {
+        (previous.is_up() != current.is_up()).then(|| Self {
+            service: service.to_string(),
+            previous: previous.clone(),
+            current: current.clone(),
+            time,
+        })
+    }
+}
+
+/// Spawn a task POSTing every `Transition` sent to the returned channel to `url` as JSON.
+///
+/// The channel is bounded so that a slow webhook can't make transitions pile up in memory.
+pub fn spawn_webhook(url: String) -> mpsc::Sender<Transition> {
+    let (sender, mut receiver) = mpsc::channel::<Transition>(32);
+    let client = reqwest::Client::new();
+
+    tokio::spawn(async move {
+        while let Some(transition) = receiver.recv().await {
+            let resp = client
+                .post(&url)
+                .json(&transition)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+            if let Err(e) = resp {
+                warn!("Couldn't send webhook for {}: {e}", transition.service);
+            }
+        }
+    });
+
+    sender
+}
+
+/// Where and how to send email notifications.
+pub struct EmailConfig {
+    pub host: String,
+    pub port: u16,
+    pub credentials: Option<(String, String)>,
+    pub from: Mailbox,
+    pub to: Mailbox,
+}
+
+/// Number of times sending an email is retried before giving up on it.
+const EMAIL_RETRIES: u32 = 3;
+
+/// Spawn a task emailing every `Transition` sent to the returned channel, as configured by
+/// `config`.
+///
+/// # Panics
+///
+/// If the SMTP host is invalid.
+pub fn spawn_email(config: EmailConfig) -> mpsc::Sender<Transition> {
+    let (sender, mut receiver) = mpsc::channel::<Transition>(32);
+    let mut transport = AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+        .expect("Invalid SMTP host")
+        .port(config.port);
+    if let Some((user, password)) = config.credentials {
+        transport = transport.credentials(Credentials::new(user, password));
+    }
+    let transport = transport.build();
+
+    tokio::spawn(async move {
+        while let Some(transition) = receiver.recv().await {
+            let email = Message::builder()
+                .from(config.from.clone())
+                .to(config.to.clone())
+                .subject(format!(
+                    "{} is {}",
+                    transition.service,
+                    transition.current.kind()
+                ))
+                .body(format!(
+                    "Service {} went from \"{}\" to \"{}\" at {}.\n",
+                    transition.service, transition.previous, transition.current, transition.time
+                ))
+                .expect("Email should be valid");
+            let mut delay = Duration::from_secs(1);
+            for attempt in 0..=EMAIL_RETRIES {
+                match transport.send(email.clone()).await {
+                    Ok(_) => break,
+                    Err(e) if attempt < EMAIL_RETRIES => {
+                        warn!(
+                            "Couldn't send email for {}, retrying in {delay:?}: {e}",
+                            transition.service
+                        );
+                        tokio::time::sleep(delay).await;
+                        delay *= 2;
+                    }
+                    Err(e) => warn!("Couldn't send email for {}: {e}", transition.service),
+                }
+            }
+        }
+    });
+
+    sender
+}
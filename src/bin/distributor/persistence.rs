@@ -0,0 +1,84 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+};
+use swec::Service;
+#[cfg(feature = "binary")]
+use tracing::warn;
+
+/// Load the services from the state file at `path`.
+///
+/// The state file is JSON, or bincode when the `binary` feature is enabled, in which case JSON
+/// state files are still accepted. Returns an empty map if the file doesn't exist yet.
+///
+/// # Errors
+///
+/// If the file exists but can't be read or deserialized.
+pub fn load(path: &Path) -> io::Result<BTreeMap<String, Service>> {
+    match fs::read(path) {
+        Ok(content) => deserialize(&content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Save the services to the state file at `path`.
+///
+/// The state is first written and synced to a temporary file next to `path` which is then
+/// renamed, so that the state file is never left half-written, even on power loss.
+///
+/// # Errors
+///
+/// If the temporary file can't be written, synced or renamed.
+pub fn save(path: &Path, services: &BTreeMap<String, Service>) -> io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&serialize(services)?)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)?;
+    sync_parent_dir(path)
+}
+
+/// Sync the directory containing `path`, so that a rename to `path` is durable.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+/// Directories can't be opened to be synced on other platforms.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(not(feature = "binary"))]
+fn serialize(services: &BTreeMap<String, Service>) -> io::Result<Vec<u8>> {
+    Ok(serde_json::to_vec(services)?)
+}
+
+#[cfg(not(feature = "binary"))]
+fn deserialize(bytes: &[u8]) -> io::Result<BTreeMap<String, Service>> {
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+#[cfg(feature = "binary")]
+fn serialize(services: &BTreeMap<String, Service>) -> io::Result<Vec<u8>> {
+    bincode::serialize(services).map_err(io::Error::other)
+}
+
+/// Deserialize bincode, falling back to JSON so that state files saved without the `binary`
+/// feature can still be loaded. They are saved as bincode from then on.
+#[cfg(feature = "binary")]
+fn deserialize(bytes: &[u8]) -> io::Result<BTreeMap<String, Service>> {
+    bincode::deserialize(bytes).or_else(|e| {
+        let services = serde_json::from_slice(bytes).map_err(|_| io::Error::other(e))?;
+        warn!("State file is JSON, it will be converted to bincode when saved");
+        Ok(services)
+    })
+}
@@ -0,0 +1,222 @@
+use crate::api_util::ApiError;
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use percent_encoding::percent_decode_str;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Number of services above which idle buckets are dropped, so that requests for many different
+/// names can't make the map grow forever.
+const MAX_IDLE_BUCKETS: usize = 1024;
+
+/// Per-service token buckets limiting how often each service can be written to.
+#[derive(Clone)]
+pub struct RateLimiter {
+    writes_per_second: f64,
+    buckets: Arc<Mutex<HashMap<BucketKey, Bucket>>>,
+}
+
+/// What a bucket limits the writes to.
+#[derive(PartialEq, Eq, Hash)]
+enum BucketKey {
+    Service(String),
+    /// Bulk creation isn't specific to a service, so all such requests share a bucket.
+    BulkCreate,
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(writes_per_second: u32) -> Self {
+        Self {
+            writes_per_second: writes_per_second.into(),
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Take a token from the bucket of `key` at `now`.
+    ///
+    /// # Errors
+    ///
+    /// If the bucket is empty, with the number of seconds until a token is available again.
+    fn acquire(&self, key: BucketKey, now: Instant) -> Result<(), u64> {
+        self.with_bucket(key, now, |bucket| {
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                Ok(())
+            } else {
+                let wait = (1.0 - bucket.tokens) / self.writes_per_second;
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                Err(wait.ceil() as u64)
+            }
+        })
+    }
+
+    /// Take `count` more tokens from the bucket of the service `name`, e.g. for the statuses of a
+    /// batch beyond the one the request was charged for.
+    ///
+    /// The bucket can go below zero, in which case the next writes are rejected until the tokens
+    /// are earned back.
+    pub fn charge(&self, name: &str, count: usize) {
+        self.charge_at(name, count, Instant::now());
+    }
+
+    /// Like `charge`, at `now`.
+    fn charge_at(&self, name: &str, count: usize, now: Instant) {
+        #[allow(clippy::cast_precision_loss)]
+        self.with_bucket(BucketKey::Service(name.to_string()), now, |bucket| {
+            bucket.tokens -= count as f64;
+        });
+    }
+
+    /// Run `f` on the bucket of `key` once refilled at `now`, creating it if needed.
+    fn with_bucket<T>(&self, key: BucketKey, now: Instant, f: impl FnOnce(&mut Bucket) -> T) -> T {
+        let mut buckets = self.buckets.lock().expect("Rate limiter mutex is poisoned");
+        if buckets.len() >= MAX_IDLE_BUCKETS && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.writes_per_second);
+        }
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: self.writes_per_second,
+            updated_at: now,
+        });
+        self.refill(bucket, now);
+        f(bucket)
+    }
+
+    /// Add the tokens earned since the last update to `bucket`, and get its new token count.
+    fn refill(&self, bucket: &mut Bucket, now: Instant) -> f64 {
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = elapsed
+            .mul_add(self.writes_per_second, bucket.tokens)
+            .min(self.writes_per_second);
+        bucket.updated_at = now;
+        bucket.tokens
+    }
+}
+
+/// Middleware rejecting writes to a service that has already been written to too often.
+///
+/// Each write request takes one token. The limiter is added to the request extensions so that
+/// handlers writing many statuses at once can `charge` for the others.
+///
+/// # Errors
+///
+/// If the request is a write and the service has no tokens left.
+pub async fn limit_writes(
+    State(limiter): State<RateLimiter>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD);
+    if !is_read {
+        let path = request.uri().path();
+        let key = if path == "/services/bulk" {
+            BucketKey::BulkCreate
+        } else {
+            // Other write routes all start with the service name, which is decoded like `Path`
+            // does so that differently encoded names share a bucket.
+            let segment = path
+                .trim_start_matches('/')
+                .split('/')
+                .next()
+                .unwrap_or_default();
+            BucketKey::Service(percent_decode_str(segment).decode_utf8_lossy().into_owned())
+        };
+        limiter
+            .acquire(key, Instant::now())
+            .map_err(|retry_after| ApiError::RateLimited { retry_after })?;
+    }
+    request.extensions_mut().insert(limiter);
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn service(name: &str) -> BucketKey {
+        BucketKey::Service(name.to_string())
+    }
+
+    fn tokens(limiter: &RateLimiter, key: &BucketKey) -> f64 {
+        limiter.buckets.lock().unwrap()[key].tokens
+    }
+
+    #[test]
+    fn refill_is_capped() {
+        let limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        assert_eq!(limiter.acquire(service("a"), start), Ok(()));
+        assert_eq!(limiter.acquire(service("a"), start), Ok(()));
+        assert_eq!(limiter.acquire(service("a"), start), Err(1));
+        // Idling earns at most `writes_per_second` tokens.
+        let later = start + Duration::from_secs(60);
+        assert_eq!(limiter.acquire(service("a"), later), Ok(()));
+        assert!((tokens(&limiter, &service("a")) - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn charge_can_go_below_zero() {
+        let limiter = RateLimiter::new(1);
+        let start = Instant::now();
+        assert_eq!(limiter.acquire(service("a"), start), Ok(()));
+        limiter.charge_at("a", 3, start);
+        assert!((tokens(&limiter, &service("a")) + 3.0).abs() < f64::EPSILON);
+        // The 3 tokens owed must be earned back before the next one.
+        assert_eq!(limiter.acquire(service("a"), start), Err(4));
+        let later = start + Duration::from_secs(3);
+        assert_eq!(limiter.acquire(service("a"), later), Err(1));
+        let later = start + Duration::from_secs(4);
+        assert_eq!(limiter.acquire(service("a"), later), Ok(()));
+        // Other services aren't charged.
+        assert_eq!(limiter.acquire(service("b"), start), Ok(()));
+    }
+
+    #[test]
+    fn retry_after_is_rounded_up() {
+        let limiter = RateLimiter::new(4);
+        let start = Instant::now();
+        for _ in 0..4 {
+            assert_eq!(limiter.acquire(service("a"), start), Ok(()));
+        }
+        // A token is earned back after a quarter of a second.
+        assert_eq!(limiter.acquire(service("a"), start), Err(1));
+
+        let limiter = RateLimiter::new(1);
+        assert_eq!(limiter.acquire(service("a"), start), Ok(()));
+        limiter.charge_at("a", 1, start);
+        let later = start + Duration::from_millis(500);
+        // 1.5 tokens are missing, earned back in 1.5 seconds.
+        assert_eq!(limiter.acquire(service("a"), later), Err(2));
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        let limiter = RateLimiter::new(1);
+        let start = Instant::now();
+        for i in 0..MAX_IDLE_BUCKETS {
+            assert_eq!(limiter.acquire(service(&i.to_string()), start), Ok(()));
+        }
+        // The buckets are still refilling, so none of them is dropped.
+        let soon = start + Duration::from_millis(500);
+        assert_eq!(limiter.acquire(service("new"), soon), Ok(()));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_IDLE_BUCKETS + 1);
+        // Once full, the buckets are dropped, except those that were just used.
+        let later = start + Duration::from_secs(1);
+        assert_eq!(limiter.acquire(service("newer"), later), Ok(()));
+        let buckets = limiter.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), 2);
+        assert!(buckets.contains_key(&service("new")) && buckets.contains_key(&service("newer")));
+    }
+}
@@ -1,17 +1,37 @@
+use crate::{notifier::Transition, telemetry};
 use chrono::{DateTime, Utc};
 use std::{
     collections::{BTreeMap, VecDeque},
     error::Error,
     fmt::Display,
+    ops::RangeInclusive,
+};
+use swec::{
+    filter::{self, Predicate},
+    GroupHealth, GroupStatus, Service, ServiceAction, ServiceSpec, ServiceWithStatus, SpecHistory,
+    SpecValidationError, StatusStats, TimedStatus,
 };
-use swec::{Service, ServiceAction, ServiceSpec, TimedStatus};
 use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, debug_span, info, warn};
+
+/// Names of the distributor's own routes, which would shadow services with the same name.
+pub const RESERVED_NAMES: [&str; 5] = ["health", "openapi.json", "docs", "services", "groups"];
 
 #[derive(Debug)]
 struct StateActor {
     receiver: mpsc::UnboundedReceiver<StateActorMessage>,
     services: BTreeMap<String, Service>,
+    /// When each service last received a status. Services loaded with statuses count as having
+    /// received one on startup.
+    last_modified: BTreeMap<String, DateTime<Utc>>,
     cap: usize,
+    max_spec_history: usize,
+    notifiers: Vec<mpsc::Sender<Transition>>,
 }
 
 impl StateActor {
@@ -19,54 +39,227 @@ impl StateActor {
         receiver: mpsc::UnboundedReceiver<StateActorMessage>,
         services: BTreeMap<String, Service>,
         cap: usize,
+        max_spec_history: usize,
+        notifiers: Vec<mpsc::Sender<Transition>>,
     ) -> Self {
+        for name in services
+            .keys()
+            .filter(|name| RESERVED_NAMES.contains(&name.as_str()))
+        {
+            warn!("Service {name} can't be accessed as its name is reserved, rename it in the state file");
+        }
+        let now = Utc::now();
+        let last_modified = services
+            .iter()
+            .filter(|(_, service)| !service.statuses.is_empty())
+            .map(|(name, _)| (name.clone(), now))
+            .collect();
         Self {
             receiver,
             services,
+            last_modified,
             cap,
+            max_spec_history,
+            notifiers,
         }
     }
 
+    #[tracing::instrument(
+        skip(self, name, msg),
+        fields(service_name = %name, action_kind = msg.kind())
+    )]
     fn handle_write(&mut self, name: String, msg: ServiceAction) -> Result<(), WriteError> {
         match msg {
             ServiceAction::CreateService(spec) => {
+                if RESERVED_NAMES.contains(&name.as_str()) {
+                    return Err(WriteError::ReservedName);
+                }
                 if self.services.contains_key(&name) {
                     return Err(WriteError::NameConflict);
                 }
-                self.services.insert(name, Service::new(spec, self.cap));
+                spec.validate().map_err(WriteError::InvalidSpec)?;
+                let cap = spec.max_statuses.unwrap_or(self.cap);
+                self.services.insert(name, Service::new(spec, cap));
+                telemetry::record_services_count(self.services.len());
                 Ok(())
             }
-            ServiceAction::DeleteService => self
-                .services
-                .remove(&name)
-                .map_or_else(|| Err(WriteError::NotFound), |_| Ok(())),
-            ServiceAction::AddStatus(s) => self.services.get_mut(&name).map_or_else(
-                || Err(WriteError::NotFound),
-                |service| {
-                    service.statuses.push_front(s);
-                    Ok(())
-                },
-            ),
+            ServiceAction::UpdateSpec(spec) => {
+                let service = self.services.get_mut(&name).ok_or(WriteError::NotFound)?;
+                let spec = service.spec.merge(&spec);
+                spec.validate().map_err(WriteError::InvalidSpec)?;
+                service.set_spec(spec, self.max_spec_history);
+                Ok(())
+            }
+            ServiceAction::ReplaceSpec(spec) => {
+                let service = self.services.get_mut(&name).ok_or(WriteError::NotFound)?;
+                spec.validate().map_err(WriteError::InvalidSpec)?;
+                service.set_spec(spec, self.max_spec_history);
+                Ok(())
+            }
+            ServiceAction::SetMaintenance(window) => {
+                let service = self.services.get_mut(&name).ok_or(WriteError::NotFound)?;
+                let spec = ServiceSpec {
+                    maintenance: Some(window),
+                    ..service.spec.clone()
+                };
+                spec.validate().map_err(WriteError::InvalidSpec)?;
+                service.set_spec(spec, self.max_spec_history);
+                Ok(())
+            }
+            ServiceAction::DeleteService => {
+                self.services.remove(&name).ok_or(WriteError::NotFound)?;
+                self.last_modified.remove(&name);
+                telemetry::record_services_count(self.services.len());
+                Ok(())
+            }
+            ServiceAction::AddStatus(s) => {
+                let service = self.services.get_mut(&name).ok_or(WriteError::NotFound)?;
+                let transition = service.current_status().and_then(|previous| {
+                    Transition::detect(&name, &previous.inner, &s.inner, s.time)
+                });
+                telemetry::record_status(&name, &s.inner);
+                let cap = service.spec.max_statuses.unwrap_or(self.cap);
+                service.push_status(s, cap);
+                self.last_modified.insert(name.clone(), Utc::now());
+                if let Some(transition) = transition {
+                    self.notify(&transition);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Insert statuses imported from another system at their place in time, without notifying of
+    /// the transitions between them since they are past ones.
+    #[tracing::instrument(skip(self, name, statuses), fields(service_name = %name))]
+    fn handle_import_statuses(
+        &mut self,
+        name: &str,
+        statuses: Vec<TimedStatus>,
+    ) -> Result<(), WriteError> {
+        let service = self.services.get_mut(name).ok_or(WriteError::NotFound)?;
+        let cap = service.spec.max_statuses.unwrap_or(self.cap);
+        for status in statuses {
+            let inner = status.inner.clone();
+            if service.insert_status(status, cap) {
+                telemetry::record_status(name, &inner);
+            }
         }
+        self.last_modified.insert(name.to_string(), Utc::now());
+        Ok(())
     }
 
-    fn handle_get_spec(&mut self, name: &str) -> Result<ServiceSpec, ServiceNotFoundError> {
+    /// Create all the services if none of them conflicts with an existing one or has an invalid
+    /// spec, and get the result for each service.
+    #[tracing::instrument(skip(self, specs), fields(service_count = specs.len()))]
+    fn handle_bulk_create(
+        &mut self,
+        specs: BTreeMap<String, ServiceSpec>,
+    ) -> BTreeMap<String, Result<(), WriteError>> {
+        let mut results: BTreeMap<_, _> = specs
+            .iter()
+            .map(|(name, spec)| {
+                let result = if RESERVED_NAMES.contains(&name.as_str()) {
+                    Err(WriteError::ReservedName)
+                } else if self.services.contains_key(name) {
+                    Err(WriteError::NameConflict)
+                } else {
+                    spec.validate().map_err(WriteError::InvalidSpec)
+                };
+                (name.clone(), result)
+            })
+            .collect();
+        if results.values().any(Result::is_err) {
+            for result in results.values_mut() {
+                if result.is_ok() {
+                    *result = Err(WriteError::BatchAborted);
+                }
+            }
+        } else {
+            for (name, spec) in specs {
+                let cap = spec.max_statuses.unwrap_or(self.cap);
+                self.services.insert(name, Service::new(spec, cap));
+            }
+            telemetry::record_services_count(self.services.len());
+        }
+        results
+    }
+
+    /// Check that the spec of the service is at version `expected`, if specified.
+    fn check_spec_version(&self, name: &str, expected: Option<u64>) -> Result<(), WriteError> {
+        match (expected, self.services.get(name)) {
+            (Some(expected), Some(service)) if service.spec_version != expected => {
+                Err(WriteError::SpecVersionMismatch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Send `transition` to all notifiers without waiting for them.
+    fn notify(&self, transition: &Transition) {
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.try_send(transition.clone()) {
+                warn!("Dropping notification for {}: {e}", transition.service);
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self, name), fields(service_name = %name))]
+    fn handle_get_spec(&mut self, name: &str) -> Result<(ServiceSpec, u64), ServiceNotFoundError> {
         self.services
             .get(name)
-            .map(|s| s.spec.clone())
+            .map(|s| (s.spec.clone(), s.spec_version))
             .ok_or(ServiceNotFoundError)
     }
 
+    #[tracing::instrument(skip(self, name, pred), fields(service_name = %name))]
     fn handle_get_statuses(
         &mut self,
         name: &str,
+        pred: &Predicate,
+        offset: usize,
+        limit: Option<usize>,
+        oldest_first: bool,
     ) -> Result<VecDeque<TimedStatus>, ServiceNotFoundError> {
         self.services
             .get(name)
-            .map(|s| s.statuses.clone())
+            .map(|s| {
+                // Statuses are stored newest first.
+                let statuses: Box<dyn Iterator<Item = &TimedStatus>> = if oldest_first {
+                    Box::new(s.statuses.iter().rev())
+                } else {
+                    Box::new(s.statuses.iter())
+                };
+                filter::apply(pred, statuses)
+                    .skip(offset)
+                    .take(limit.unwrap_or(usize::MAX))
+                    .cloned()
+                    .collect()
+            })
             .ok_or(ServiceNotFoundError)
     }
 
+    #[tracing::instrument(skip(self, name, range), fields(service_name = %name))]
+    fn handle_get_stats(
+        &mut self,
+        name: &str,
+        range: &RangeInclusive<DateTime<Utc>>,
+    ) -> Result<StatusStats, ServiceNotFoundError> {
+        self.services
+            .get(name)
+            .map(|s| {
+                // Statuses are stored newest first.
+                StatusStats::from_statuses(
+                    s.statuses.iter().rev().filter(|status| {
+                        range.contains(&status.time) && !s.spec.is_in_maintenance(status.time)
+                    }),
+                    (*range.end()).min(Utc::now()),
+                )
+            })
+            .ok_or(ServiceNotFoundError)
+    }
+
+    #[tracing::instrument(skip(self, name), fields(service_name = %name))]
     fn handle_get_status_at(
         &mut self,
         name: &str,
@@ -85,8 +278,147 @@ impl StateActor {
             .ok_or(ServiceNotFoundError)
     }
 
+    #[tracing::instrument(skip(self, name), fields(service_name = %name))]
+    fn handle_get_latest_status(
+        &mut self,
+        name: &str,
+    ) -> Result<Option<TimedStatus>, ServiceNotFoundError> {
+        self.services
+            .get(name)
+            .map(|s| s.current_status().cloned())
+            .ok_or(ServiceNotFoundError)
+    }
+
+    #[tracing::instrument(skip(self, name), fields(service_name = %name))]
+    fn handle_get_spec_history(&mut self, name: &str) -> Result<SpecHistory, ServiceNotFoundError> {
+        self.services
+            .get(name)
+            .map(|s| s.spec_history.clone())
+            .ok_or(ServiceNotFoundError)
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn handle_list_services(
+        &mut self,
+        tag: Option<&str>,
+        labels: &BTreeMap<String, String>,
+    ) -> Vec<String> {
+        self.services
+            .iter()
+            .filter(|(_, service)| tag.is_none_or(|tag| service.spec.tags.iter().any(|t| t == tag)))
+            .filter(|(_, service)| {
+                labels
+                    .iter()
+                    .all(|(key, value)| service.spec.labels.get(key) == Some(value))
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn handle_list_services_with_status(&mut self) -> BTreeMap<String, ServiceWithStatus> {
+        self.services
+            .iter()
+            .map(|(name, service)| (name.clone(), service.into()))
+            .collect()
+    }
+
+    /// Get the latest status of each service whose last status was received before
+    /// `received_before`.
+    #[tracing::instrument(skip(self))]
+    fn handle_list_stale(&mut self, received_before: DateTime<Utc>) -> Vec<(String, TimedStatus)> {
+        self.last_modified
+            .iter()
+            .filter(|(_, received)| **received < received_before)
+            .filter_map(|(name, _)| {
+                let status = self.services.get(name)?.current_status()?;
+                Some((name.clone(), status.clone()))
+            })
+            .collect()
+    }
+
+    /// Aggregate the status of services by group. Services without a group are omitted.
+    #[tracing::instrument(skip(self))]
+    fn handle_list_groups(&mut self) -> BTreeMap<String, GroupStatus> {
+        let mut groups = BTreeMap::<String, GroupStatus>::new();
+        for (name, service) in &self.services {
+            if let Some(group) = &service.spec.group {
+                let is_up = service.current_status().is_some_and(|s| s.inner.is_up());
+                groups
+                    .entry(group.clone())
+                    .or_default()
+                    .add(name.clone(), is_up);
+            }
+        }
+        groups
+    }
+
+    /// Get whether each service in `group` is up, or `None` if no service is in it.
+    #[tracing::instrument(skip(self))]
+    fn handle_get_group_health(&mut self, group: &str) -> Option<GroupHealth> {
+        let services: BTreeMap<_, _> = self
+            .services
+            .iter()
+            .filter(|(_, service)| service.spec.group.as_deref() == Some(group))
+            .map(|(name, service)| {
+                let is_up = service.current_status().is_some_and(|s| s.inner.is_up());
+                (name.clone(), is_up)
+            })
+            .collect();
+        (!services.is_empty()).then(|| GroupHealth {
+            group: group.to_string(),
+            all_up: services.values().all(|is_up| *is_up),
+            services,
+        })
+    }
+
+    /// Compact the statuses of a service, and get their number before and after.
+    #[tracing::instrument(skip(self, name), fields(service_name = %name))]
+    fn handle_compact_statuses(
+        &mut self,
+        name: &str,
+    ) -> Result<(usize, usize), ServiceNotFoundError> {
+        let service = self.services.get_mut(name).ok_or(ServiceNotFoundError)?;
+        let old_len = service.statuses.len();
+        service.compact();
+        Ok((old_len, service.statuses.len()))
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn handle_trim_statuses(&mut self, older_than: DateTime<Utc>) {
+        for service in self.services.values_mut() {
+            service.retention_trim(older_than);
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn handle_snapshot(&mut self) -> BTreeMap<String, Service> {
+        self.services.clone()
+    }
+
+    #[tracing::instrument(skip(self))]
+    fn handle_diff_snapshot(&mut self, since: DateTime<Utc>) -> Vec<(String, Vec<TimedStatus>)> {
+        self.last_modified
+            .iter()
+            .filter(|(_, modified)| **modified > since)
+            .filter_map(|(name, _)| {
+                let statuses = self
+                    .services
+                    .get(name)?
+                    .received_since(since)
+                    .cloned()
+                    .collect();
+                Some((name.clone(), statuses))
+            })
+            .collect()
+    }
+
     async fn run(&mut self) {
+        telemetry::record_services_count(self.services.len());
+        let mut shutdown_responders = Vec::new();
         while let Some(msg) = self.receiver.recv().await {
+            let _span = debug_span!("message").entered();
+            debug!("Handling {}", msg.kind());
             // Errors when sending can happen e.g. if the `select!` macro is used to cancel waiting
             // for the response. We can safely ignore these.
             match msg {
@@ -94,11 +426,55 @@ impl StateActor {
                     respond_to,
                     name,
                     action,
+                    if_spec_version,
+                } => {
+                    let resp = self
+                        .check_spec_version(&name, if_spec_version)
+                        .and_then(|()| self.handle_write(name, action));
+                    let _ = respond_to.send(resp);
+                }
+                StateActorMessage::BatchWrite {
+                    actions,
+                    respond_to,
                 } => {
-                    let _ = respond_to.send(self.handle_write(name, action));
+                    let resp = actions
+                        .into_iter()
+                        .map(|(name, action)| self.handle_write(name, action))
+                        .collect();
+                    let _ = respond_to.send(resp);
                 }
-                StateActorMessage::GetStatuses { name, respond_to } => {
-                    let _ = respond_to.send(self.handle_get_statuses(&name));
+                StateActorMessage::ImportStatuses {
+                    name,
+                    statuses,
+                    respond_to,
+                } => {
+                    let _ = respond_to.send(self.handle_import_statuses(&name, statuses));
+                }
+                StateActorMessage::BulkCreate { specs, respond_to } => {
+                    let _ = respond_to.send(self.handle_bulk_create(specs));
+                }
+                StateActorMessage::GetStatuses {
+                    name,
+                    pred,
+                    offset,
+                    limit,
+                    oldest_first,
+                    respond_to,
+                } => {
+                    let _ = respond_to.send(self.handle_get_statuses(
+                        &name,
+                        &pred,
+                        offset,
+                        limit,
+                        oldest_first,
+                    ));
+                }
+                StateActorMessage::GetStats {
+                    name,
+                    range,
+                    respond_to,
+                } => {
+                    let _ = respond_to.send(self.handle_get_stats(&name, &range));
                 }
                 StateActorMessage::GetStatusAt {
                     name,
@@ -107,11 +483,63 @@ impl StateActor {
                 } => {
                     let _ = respond_to.send(self.handle_get_status_at(&name, time));
                 }
+                StateActorMessage::GetLatestStatus { name, respond_to } => {
+                    let _ = respond_to.send(self.handle_get_latest_status(&name));
+                }
                 StateActorMessage::GetSpec { name, respond_to } => {
                     let _ = respond_to.send(self.handle_get_spec(&name));
                 }
+                StateActorMessage::GetSpecHistory { name, respond_to } => {
+                    let _ = respond_to.send(self.handle_get_spec_history(&name));
+                }
+                StateActorMessage::ListServices {
+                    tag,
+                    labels,
+                    respond_to,
+                } => {
+                    let _ = respond_to.send(self.handle_list_services(tag.as_deref(), &labels));
+                }
+                StateActorMessage::ListServicesWithStatus { respond_to } => {
+                    let _ = respond_to.send(self.handle_list_services_with_status());
+                }
+                StateActorMessage::ListStale {
+                    received_before,
+                    respond_to,
+                } => {
+                    let _ = respond_to.send(self.handle_list_stale(received_before));
+                }
+                StateActorMessage::ListGroups { respond_to } => {
+                    let _ = respond_to.send(self.handle_list_groups());
+                }
+                StateActorMessage::GetGroupHealth { group, respond_to } => {
+                    let _ = respond_to.send(self.handle_get_group_health(&group));
+                }
+                StateActorMessage::CompactStatuses { name, respond_to } => {
+                    let _ = respond_to.send(self.handle_compact_statuses(&name));
+                }
+                StateActorMessage::TrimStatuses {
+                    older_than,
+                    respond_to,
+                } => {
+                    self.handle_trim_statuses(older_than);
+                    let _ = respond_to.send(());
+                }
+                StateActorMessage::Snapshot { respond_to } => {
+                    let _ = respond_to.send(self.handle_snapshot());
+                }
+                StateActorMessage::DiffSnapshot { since, respond_to } => {
+                    let _ = respond_to.send(self.handle_diff_snapshot(since));
+                }
+                StateActorMessage::Shutdown { respond_to } => {
+                    // Stop accepting new messages, but keep handling the ones already queued.
+                    self.receiver.close();
+                    shutdown_responders.push(respond_to);
+                }
             };
         }
+        for respond_to in shutdown_responders {
+            let _ = respond_to.send(self.handle_snapshot());
+        }
     }
 }
 
@@ -119,25 +547,69 @@ impl StateActor {
 pub struct StateActorHandle {
     mpsc_sender: mpsc::UnboundedSender<StateActorMessage>,
     broadcast_sender: broadcast::Sender<(String, ServiceAction)>,
+    /// Cancelled to end the streams of actions, which otherwise never end.
+    streams_closed: CancellationToken,
 }
 
 impl StateActorHandle {
     /// Create a new state instance and return its handle.
-    pub fn new(services: BTreeMap<String, Service>, cap: usize) -> Self {
+    ///
+    /// Status transitions are sent to each of the `notifiers`. Subscribers lagging more than
+    /// `broadcast_capacity` actions behind miss the oldest ones. At most `max_spec_history`
+    /// previous specs are kept per service.
+    pub fn new(
+        services: BTreeMap<String, Service>,
+        cap: usize,
+        max_spec_history: usize,
+        notifiers: Vec<mpsc::Sender<Transition>>,
+        broadcast_capacity: usize,
+    ) -> Self {
         let (mpsc_sender, mpsc_receiver) = mpsc::unbounded_channel();
-        let mut actor = StateActor::new(mpsc_receiver, services, cap);
+        let mut actor = StateActor::new(mpsc_receiver, services, cap, max_spec_history, notifiers);
         tokio::spawn(async move { actor.run().await });
 
-        let broadcast_sender = broadcast::Sender::new(32);
+        let broadcast_sender = broadcast::Sender::new(broadcast_capacity);
 
         Self {
             mpsc_sender,
             broadcast_sender,
+            streams_closed: CancellationToken::new(),
         }
     }
 
-    pub fn subscribe(&self) -> broadcast::Receiver<(String, ServiceAction)> {
-        self.broadcast_sender.subscribe()
+    /// Stream the actions executed from now on, along with the name of the service, until
+    /// `close_streams` is called.
+    pub fn watch_actions(
+        &self,
+    ) -> impl Stream<Item = Result<(String, ServiceAction), BroadcastStreamRecvError>> {
+        futures_util::StreamExt::take_until(
+            BroadcastStream::new(self.broadcast_sender.subscribe()),
+            self.streams_closed.clone().cancelled_owned(),
+        )
+    }
+
+    /// End all the streams returned by the `watch_*` methods, e.g. so that the connections
+    /// streaming them don't keep a graceful shutdown waiting.
+    pub fn close_streams(&self) {
+        self.streams_closed.cancel();
+    }
+
+    /// Stream the statuses added to any service from now on, along with the name of the service.
+    pub fn watch_statuses(&self) -> impl Stream<Item = (String, TimedStatus)> {
+        self.watch_actions().filter_map(|msg| match msg {
+            Ok((name, ServiceAction::AddStatus(status))) => Some((name, status)),
+            Ok(_) => None,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                warn!("Status stream lagged behind, {skipped} actions were dropped");
+                None
+            }
+        })
+    }
+
+    /// Stream the statuses added to the service `name` from now on.
+    pub fn watch_service(&self, name: String) -> impl Stream<Item = TimedStatus> {
+        self.watch_statuses()
+            .filter_map(move |(service_name, status)| (service_name == name).then_some(status))
     }
 
     async fn exchange<R: Send>(&self, msg: StateActorMessage, recv: oneshot::Receiver<R>) -> R {
@@ -152,20 +624,51 @@ impl StateActorHandle {
     ///
     /// # Errors
     ///
-    /// If there is a name conflict (in the case `CreateService`) or if the specified service
-    /// doesn't exist (other cases).
+    /// If there is a name conflict (in the case `CreateService`), if the specified service
+    /// doesn't exist (other cases) or if the new spec is invalid.
     pub async fn write(&self, name: String, action: ServiceAction) -> Result<(), WriteError> {
+        self.send_write(name, action, None).await
+    }
+
+    /// Like `write`, but only if the spec of the service is still at version `spec_version`.
+    ///
+    /// # Errors
+    ///
+    /// Same as `write`, or if the spec version doesn't match.
+    pub async fn write_if_spec_version(
+        &self,
+        name: String,
+        action: ServiceAction,
+        spec_version: u64,
+    ) -> Result<(), WriteError> {
+        self.send_write(name, action, Some(spec_version)).await
+    }
+
+    #[tracing::instrument(
+        skip(self, name, action),
+        fields(service_name = %name, action_kind = action.kind())
+    )]
+    async fn send_write(
+        &self,
+        name: String,
+        action: ServiceAction,
+        if_spec_version: Option<u64>,
+    ) -> Result<(), WriteError> {
         let (send, recv) = oneshot::channel();
 
         let msg = StateActorMessage::Write {
             name: name.clone(),
             action: action.clone(),
+            if_spec_version,
             respond_to: send,
         };
 
         let resp = self.exchange(msg, recv).await;
 
-        if resp.is_ok() {
+        if let Err(e) = resp {
+            info!("Couldn't execute action: {e}");
+        } else {
+            info!("Action executed");
             // If this fails, there just aren't any subscribers to send messages to.
             let _ = self.broadcast_sender.send((name, action));
         }
@@ -173,14 +676,163 @@ impl StateActorHandle {
         resp
     }
 
+    /// Run all the specified actions in order, without any other message being handled in between.
+    ///
+    /// Returns the result of each action, in the same order. A failing action doesn't prevent the
+    /// next ones from running.
+    pub async fn batch_write(
+        &self,
+        actions: Vec<(String, ServiceAction)>,
+    ) -> Vec<Result<(), WriteError>> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::BatchWrite {
+            actions: actions.clone(),
+            respond_to: send,
+        };
+
+        let resps = self.exchange(msg, recv).await;
+
+        info!(
+            "Batch of {} actions executed, {} failed",
+            resps.len(),
+            resps.iter().filter(|r| r.is_err()).count()
+        );
+        for (action, resp) in actions.into_iter().zip(&resps) {
+            if resp.is_ok() {
+                // If this fails, there just aren't any subscribers to send messages to.
+                let _ = self.broadcast_sender.send(action);
+            }
+        }
+
+        resps
+    }
+
+    /// Add statuses imported from another system to the service `name`, at their place in time
+    /// and without notifying of the transitions between them.
+    ///
+    /// # Errors
+    ///
+    /// If the service doesn't exist.
+    pub async fn import_statuses(
+        &self,
+        name: String,
+        statuses: Vec<TimedStatus>,
+    ) -> Result<(), WriteError> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::ImportStatuses {
+            name: name.clone(),
+            statuses: statuses.clone(),
+            respond_to: send,
+        };
+
+        let resp = self.exchange(msg, recv).await;
+
+        if let Err(e) = &resp {
+            info!("Couldn't import statuses: {e}");
+        } else {
+            info!("{} statuses imported", statuses.len());
+            for status in statuses {
+                // If this fails, there just aren't any subscribers to send messages to.
+                let _ = self
+                    .broadcast_sender
+                    .send((name.clone(), ServiceAction::AddStatus(status)));
+            }
+        }
+
+        resp
+    }
+
+    /// Create all the services, or none of them if any can't be created.
+    ///
+    /// Returns the result for each service. If any failed, the others fail with
+    /// `WriteError::BatchAborted`.
+    pub async fn bulk_create(
+        &self,
+        specs: BTreeMap<String, ServiceSpec>,
+    ) -> BTreeMap<String, Result<(), WriteError>> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::BulkCreate {
+            specs: specs.clone(),
+            respond_to: send,
+        };
+
+        let resps = self.exchange(msg, recv).await;
+
+        if resps.values().all(Result::is_ok) {
+            info!("{} services created", resps.len());
+            for (name, spec) in specs {
+                // If this fails, there just aren't any subscribers to send messages to.
+                let _ = self
+                    .broadcast_sender
+                    .send((name, ServiceAction::CreateService(spec)));
+            }
+        } else {
+            info!("Couldn't create services");
+        }
+
+        resps
+    }
+
+    /// Get the statuses of a service that satisfy `pred`, newest first, skipping the `offset`
+    /// newest ones and returning at most `limit` of them.
     pub async fn get_statuses(
         &self,
         name: String,
+        pred: Predicate,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Result<VecDeque<TimedStatus>, ServiceNotFoundError> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::GetStatuses {
+            name,
+            pred,
+            offset,
+            limit,
+            oldest_first: false,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Like `get_statuses`, but oldest first, skipping the `offset` oldest statuses.
+    pub async fn get_oldest_statuses(
+        &self,
+        name: String,
+        pred: Predicate,
+        offset: usize,
+        limit: Option<usize>,
     ) -> Result<VecDeque<TimedStatus>, ServiceNotFoundError> {
         let (send, recv) = oneshot::channel();
 
         let msg = StateActorMessage::GetStatuses {
             name,
+            pred,
+            offset,
+            limit,
+            oldest_first: true,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the stats of the statuses of a service that are within `range`, ignoring the ones
+    /// during the maintenance window.
+    pub async fn get_stats(
+        &self,
+        name: String,
+        range: RangeInclusive<DateTime<Utc>>,
+    ) -> Result<StatusStats, ServiceNotFoundError> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::GetStats {
+            name,
+            range,
             respond_to: send,
         };
 
@@ -203,7 +855,22 @@ impl StateActorHandle {
         self.exchange(msg, recv).await
     }
 
-    pub async fn get_spec(&self, name: String) -> Result<ServiceSpec, ServiceNotFoundError> {
+    pub async fn get_latest_status(
+        &self,
+        name: String,
+    ) -> Result<Option<TimedStatus>, ServiceNotFoundError> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::GetLatestStatus {
+            name,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the spec of a service along with its version.
+    pub async fn get_spec(&self, name: String) -> Result<(ServiceSpec, u64), ServiceNotFoundError> {
         let (send, recv) = oneshot::channel();
 
         let msg = StateActorMessage::GetSpec {
@@ -213,6 +880,145 @@ impl StateActorHandle {
 
         self.exchange(msg, recv).await
     }
+
+    /// Get the previous specs of a service along with the time they were replaced, newest first.
+    pub async fn get_spec_history(
+        &self,
+        name: String,
+    ) -> Result<SpecHistory, ServiceNotFoundError> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::GetSpecHistory {
+            name,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the names of all services, or only of those tagged with `tag` if specified and having
+    /// all the `labels`.
+    pub async fn list_services(
+        &self,
+        tag: Option<String>,
+        labels: BTreeMap<String, String>,
+    ) -> Vec<String> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::ListServices {
+            tag,
+            labels,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the spec and latest status of every service.
+    pub async fn list_services_with_status(&self) -> BTreeMap<String, ServiceWithStatus> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::ListServicesWithStatus { respond_to: send };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the latest status of each service whose last status was received before
+    /// `received_before`, e.g. because its checker crashed.
+    pub async fn list_stale(&self, received_before: DateTime<Utc>) -> Vec<(String, TimedStatus)> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::ListStale {
+            received_before,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the aggregated status of each group of services.
+    pub async fn list_groups(&self) -> BTreeMap<String, GroupStatus> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::ListGroups { respond_to: send };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get whether each service in `group` is up, or `None` if no service is in it.
+    pub async fn get_group_health(&self, group: String) -> Option<GroupHealth> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::GetGroupHealth {
+            group,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Keep only the statuses where a service goes up or down, and get the number of statuses
+    /// before and after.
+    pub async fn compact_statuses(
+        &self,
+        name: String,
+    ) -> Result<(usize, usize), ServiceNotFoundError> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::CompactStatuses {
+            name,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Drop the statuses older than `older_than` from all services.
+    pub async fn trim_statuses(&self, older_than: DateTime<Utc>) {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::TrimStatuses {
+            older_than,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await;
+    }
+
+    /// Get a copy of all services, e.g. to save them to disk.
+    pub async fn snapshot(&self) -> BTreeMap<String, Service> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::Snapshot { respond_to: send };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Get the statuses received since `since`, newest first, of each service that received any,
+    /// e.g. to save only what changed.
+    pub async fn diff_snapshot(&self, since: DateTime<Utc>) -> Vec<(String, Vec<TimedStatus>)> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::DiffSnapshot {
+            since,
+            respond_to: send,
+        };
+
+        self.exchange(msg, recv).await
+    }
+
+    /// Stop the actor once it has handled the messages already sent to it, and get the final
+    /// state of all services.
+    ///
+    /// Any message sent afterwards makes the sender panic, so this should only be called once
+    /// nothing else uses the actor anymore.
+    pub async fn shutdown(&self) -> BTreeMap<String, Service> {
+        let (send, recv) = oneshot::channel();
+
+        let msg = StateActorMessage::Shutdown { respond_to: send };
+
+        self.exchange(msg, recv).await
+    }
 }
 
 #[derive(Debug)]
@@ -220,27 +1026,129 @@ enum StateActorMessage {
     Write {
         name: String,
         action: ServiceAction,
+        if_spec_version: Option<u64>,
         respond_to: oneshot::Sender<Result<(), WriteError>>,
     },
+    BatchWrite {
+        actions: Vec<(String, ServiceAction)>,
+        respond_to: oneshot::Sender<Vec<Result<(), WriteError>>>,
+    },
+    ImportStatuses {
+        name: String,
+        statuses: Vec<TimedStatus>,
+        respond_to: oneshot::Sender<Result<(), WriteError>>,
+    },
+    BulkCreate {
+        specs: BTreeMap<String, ServiceSpec>,
+        respond_to: oneshot::Sender<BTreeMap<String, Result<(), WriteError>>>,
+    },
     GetStatuses {
         name: String,
+        pred: Predicate,
+        offset: usize,
+        limit: Option<usize>,
+        oldest_first: bool,
         respond_to: oneshot::Sender<Result<VecDeque<TimedStatus>, ServiceNotFoundError>>,
     },
+    GetStats {
+        name: String,
+        range: RangeInclusive<DateTime<Utc>>,
+        respond_to: oneshot::Sender<Result<StatusStats, ServiceNotFoundError>>,
+    },
     GetStatusAt {
         name: String,
         time: DateTime<Utc>,
         respond_to: oneshot::Sender<Result<Option<TimedStatus>, ServiceNotFoundError>>,
     },
+    GetLatestStatus {
+        name: String,
+        respond_to: oneshot::Sender<Result<Option<TimedStatus>, ServiceNotFoundError>>,
+    },
     GetSpec {
         name: String,
-        respond_to: oneshot::Sender<Result<ServiceSpec, ServiceNotFoundError>>,
+        respond_to: oneshot::Sender<Result<(ServiceSpec, u64), ServiceNotFoundError>>,
+    },
+    GetSpecHistory {
+        name: String,
+        respond_to: oneshot::Sender<Result<SpecHistory, ServiceNotFoundError>>,
+    },
+    ListServices {
+        tag: Option<String>,
+        labels: BTreeMap<String, String>,
+        respond_to: oneshot::Sender<Vec<String>>,
+    },
+    ListServicesWithStatus {
+        respond_to: oneshot::Sender<BTreeMap<String, ServiceWithStatus>>,
+    },
+    ListStale {
+        received_before: DateTime<Utc>,
+        respond_to: oneshot::Sender<Vec<(String, TimedStatus)>>,
+    },
+    ListGroups {
+        respond_to: oneshot::Sender<BTreeMap<String, GroupStatus>>,
+    },
+    GetGroupHealth {
+        group: String,
+        respond_to: oneshot::Sender<Option<GroupHealth>>,
+    },
+    CompactStatuses {
+        name: String,
+        respond_to: oneshot::Sender<Result<(usize, usize), ServiceNotFoundError>>,
+    },
+    TrimStatuses {
+        older_than: DateTime<Utc>,
+        respond_to: oneshot::Sender<()>,
+    },
+    Snapshot {
+        respond_to: oneshot::Sender<BTreeMap<String, Service>>,
+    },
+    DiffSnapshot {
+        since: DateTime<Utc>,
+        respond_to: oneshot::Sender<Vec<(String, Vec<TimedStatus>)>>,
+    },
+    Shutdown {
+        respond_to: oneshot::Sender<BTreeMap<String, Service>>,
     },
 }
 
+impl StateActorMessage {
+    /// Get the name of the variant, for logging.
+    const fn kind(&self) -> &'static str {
+        match self {
+            Self::Write { .. } => "write",
+            Self::BatchWrite { .. } => "batch_write",
+            Self::ImportStatuses { .. } => "import_statuses",
+            Self::BulkCreate { .. } => "bulk_create",
+            Self::GetStatuses { .. } => "get_statuses",
+            Self::GetStats { .. } => "get_stats",
+            Self::GetStatusAt { .. } => "get_status_at",
+            Self::GetLatestStatus { .. } => "get_latest_status",
+            Self::GetSpec { .. } => "get_spec",
+            Self::GetSpecHistory { .. } => "get_spec_history",
+            Self::ListServices { .. } => "list_services",
+            Self::ListServicesWithStatus { .. } => "list_services_with_status",
+            Self::ListStale { .. } => "list_stale",
+            Self::ListGroups { .. } => "list_groups",
+            Self::GetGroupHealth { .. } => "get_group_health",
+            Self::CompactStatuses { .. } => "compact_statuses",
+            Self::TrimStatuses { .. } => "trim_statuses",
+            Self::Snapshot { .. } => "snapshot",
+            Self::DiffSnapshot { .. } => "diff_snapshot",
+            Self::Shutdown { .. } => "shutdown",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum WriteError {
     NotFound,
     NameConflict,
+    /// The name is one of `RESERVED_NAMES`.
+    ReservedName,
+    InvalidSpec(SpecValidationError),
+    SpecVersionMismatch,
+    /// Not applied because another action of the same all-or-nothing batch failed.
+    BatchAborted,
 }
 
 impl Display for WriteError {
@@ -248,6 +1156,14 @@ impl Display for WriteError {
         match self {
             Self::NotFound => ServiceNotFoundError.fmt(f),
             Self::NameConflict => write!(f, "Service name conflict"),
+            Self::ReservedName => write!(
+                f,
+                "Service name is reserved, it can't be any of {}",
+                RESERVED_NAMES.join(", ")
+            ),
+            Self::InvalidSpec(e) => write!(f, "Invalid spec: {e}"),
+            Self::SpecVersionMismatch => write!(f, "Spec has been modified since it was read"),
+            Self::BatchAborted => write!(f, "Another action of the batch failed"),
         }
     }
 }
@@ -0,0 +1,62 @@
+use axum::{routing::get, Router};
+use metrics::{counter, gauge};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
+use metrics_util::MetricKindMask;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+use swec::Status;
+use tracing::info;
+
+/// The last recorded number of services, to record it again before rendering the metrics.
+static SERVICES_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Install the Prometheus recorder. Until this is called, recording metrics is a no-op.
+///
+/// If set, metrics that aren't recorded for `idle_timeout` stop being exported, so that those of
+/// deleted services don't stay frozen at their last value.
+pub fn install(idle_timeout: Option<Duration>) -> PrometheusHandle {
+    PrometheusBuilder::new()
+        .idle_timeout(MetricKindMask::ALL, idle_timeout)
+        .install_recorder()
+        .expect("Couldn't install metrics recorder")
+}
+
+/// Serve the metrics recorded by `handle` on `/metrics` at `address`.
+pub async fn serve(address: String, handle: PrometheusHandle) {
+    let app = Router::new().route(
+        "/metrics",
+        get(move || async move {
+            // The number of services rarely changes, and would otherwise be dropped as idle when
+            // an idle timeout is set.
+            record_services_count(SERVICES_COUNT.load(Ordering::Relaxed));
+            handle.render()
+        }),
+    );
+
+    info!("Binding metrics endpoint to {address}");
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .expect("Couldn't create metrics TCP listener");
+    axum::serve(listener, app)
+        .await
+        .expect("Couldn't start metrics server");
+}
+
+pub fn record_services_count(count: usize) {
+    SERVICES_COUNT.store(count, Ordering::Relaxed);
+    // Precision loss would need more than 2^52 services.
+    #[allow(clippy::cast_precision_loss)]
+    gauge!("swec_services_total").set(count as f64);
+}
+
+pub fn record_status(service: &str, status: &Status) {
+    counter!("swec_statuses_total", "service" => service.to_string(), "result" => status.kind())
+        .increment(1);
+    gauge!("swec_last_status_is_up", "service" => service.to_string()).set(if status.is_up() {
+        1.0
+    } else {
+        0.0
+    });
+}
@@ -0,0 +1,94 @@
+use crate::{api_util::Path, state_actor::StateActorHandle};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, time::Duration};
+use swec::TimedStatus;
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+
+/// How often the client is pinged. It is disconnected if it hasn't answered the previous ping.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A message from the client replacing the services it is subscribed to.
+#[derive(Deserialize)]
+struct Subscription {
+    subscribe: BTreeSet<String>,
+}
+
+#[derive(Serialize)]
+struct StatusFrame<'a> {
+    service: &'a str,
+    status: &'a TimedStatus,
+}
+
+/// Stream the statuses added to the service over a WebSocket, as `{"service": ..., "status": ...}`
+/// frames.
+///
+/// The client can send `{"subscribe": [<names>]}` to get the statuses of other services instead.
+#[utoipa::path(
+    get,
+    path = "/{name}/ws",
+    params(("name" = String, Path, description = "Name of the service to subscribe to first")),
+    responses((status = 101, description = "Switching to the WebSocket protocol"))
+)]
+pub async fn get_ws(
+    State(state_actor_handle): State<StateActorHandle>,
+    Path(name): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| handle_socket(socket, state_actor_handle, name))
+}
+
+async fn handle_socket(mut socket: WebSocket, state_actor_handle: StateActorHandle, name: String) {
+    let mut subscribed = BTreeSet::from([name]);
+    let mut statuses = Box::pin(state_actor_handle.watch_statuses());
+    let mut ping_interval = tokio::time::interval(PING_INTERVAL);
+    let mut awaiting_pong = false;
+
+    loop {
+        tokio::select! {
+            next = statuses.next() => {
+                // The stream ends when the distributor shuts down.
+                let Some((service, status)) = next else {
+                    break;
+                };
+                if !subscribed.contains(&service) {
+                    continue;
+                }
+                let frame = serde_json::to_string(&StatusFrame {
+                    service: &service,
+                    status: &status,
+                })
+                .expect("Status should be serializable");
+                if socket.send(Message::Text(frame)).await.is_err() {
+                    break;
+                }
+            }
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Subscription>(&text) {
+                    Ok(subscription) => subscribed = subscription.subscribe,
+                    Err(e) => warn!("Invalid WebSocket message: {e}"),
+                },
+                Some(Ok(Message::Pong(_))) => awaiting_pong = false,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = ping_interval.tick() => {
+                if awaiting_pong {
+                    info!("Closing WebSocket whose client stopped answering pings");
+                    break;
+                }
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+                awaiting_pong = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,205 @@
+//! A typed client for the distributor API.
+
+use crate::{ApiErrorBody, ServiceAction, ServiceSpec, TimedStatus};
+use reqwest::{Method, RequestBuilder, Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use std::{collections::VecDeque, error::Error, fmt::Display};
+
+/// A client for the distributor API at `base_url`.
+#[derive(Debug, Clone)]
+pub struct SwecClient {
+    base_url: Url,
+    http: reqwest::Client,
+    api_key: Option<String>,
+}
+
+impl SwecClient {
+    /// Create a client for the distributor at `base_url`, e.g. `http://localhost:8080`.
+    ///
+    /// # Panics
+    ///
+    /// If `base_url` can't have a path, e.g. `mailto:` URLs.
+    #[must_use]
+    pub fn new(base_url: Url) -> Self {
+        assert!(!base_url.cannot_be_a_base(), "Invalid distributor URL");
+        Self {
+            base_url,
+            http: reqwest::Client::new(),
+            api_key: None,
+        }
+    }
+
+    /// Send `api_key` as a bearer token with every request.
+    #[must_use]
+    pub fn with_api_key(mut self, api_key: String) -> Self {
+        self.api_key = Some(api_key);
+        self
+    }
+
+    /// Get the names of all services.
+    ///
+    /// # Errors
+    ///
+    /// If the request fails.
+    pub async fn list_services(&self) -> Result<Vec<String>, SwecClientError> {
+        self.get_json(&[""]).await
+    }
+
+    /// Get the spec of a service.
+    ///
+    /// # Errors
+    ///
+    /// If the service doesn't exist or the request fails.
+    pub async fn get_spec(&self, name: &str) -> Result<ServiceSpec, SwecClientError> {
+        self.get_json(&[name, "spec"]).await
+    }
+
+    /// Get the statuses of a service, newest first.
+    ///
+    /// # Errors
+    ///
+    /// If the service doesn't exist or the request fails.
+    pub async fn get_statuses(&self, name: &str) -> Result<VecDeque<TimedStatus>, SwecClientError> {
+        self.get_json(&[name, "statuses"]).await
+    }
+
+    /// Get the most recent status of a service, if it has any.
+    ///
+    /// # Errors
+    ///
+    /// If the service doesn't exist or the request fails.
+    pub async fn get_latest_status(
+        &self,
+        name: &str,
+    ) -> Result<Option<TimedStatus>, SwecClientError> {
+        let resp = self
+            .send(self.request(Method::GET, &[name, "status", "latest"]))
+            .await?;
+        if resp.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        Ok(Some(resp.json().await?))
+    }
+
+    /// Create a service.
+    ///
+    /// # Errors
+    ///
+    /// If a service with this name already exists, the spec is invalid or the request fails.
+    pub async fn create_service(
+        &self,
+        name: &str,
+        spec: ServiceSpec,
+    ) -> Result<(), SwecClientError> {
+        self.write(name, &ServiceAction::CreateService(spec)).await
+    }
+
+    /// Delete a service.
+    ///
+    /// # Errors
+    ///
+    /// If the service doesn't exist or the request fails.
+    pub async fn delete_service(&self, name: &str) -> Result<(), SwecClientError> {
+        self.send(self.request(Method::DELETE, &[name])).await?;
+        Ok(())
+    }
+
+    /// Add a status to a service.
+    ///
+    /// # Errors
+    ///
+    /// If the service doesn't exist or the request fails.
+    pub async fn post_status(
+        &self,
+        name: &str,
+        status: TimedStatus,
+    ) -> Result<(), SwecClientError> {
+        self.write(name, &ServiceAction::AddStatus(status)).await
+    }
+
+    async fn write(&self, name: &str, action: &ServiceAction) -> Result<(), SwecClientError> {
+        self.send(self.request(Method::PUT, &[name]).json(action))
+            .await?;
+        Ok(())
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &[&str]) -> Result<T, SwecClientError> {
+        Ok(self
+            .send(self.request(Method::GET, path))
+            .await?
+            .json()
+            .await?)
+    }
+
+    fn request(&self, method: Method, path: &[&str]) -> RequestBuilder {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("Base URL was checked in `new`")
+            .pop_if_empty()
+            .extend(path);
+        let req = self.http.request(method, url);
+        match &self.api_key {
+            Some(api_key) => req.bearer_auth(api_key),
+            None => req,
+        }
+    }
+
+    async fn send(&self, req: RequestBuilder) -> Result<Response, SwecClientError> {
+        let resp = req.send().await?;
+        match resp.status() {
+            status if status.is_success() => Ok(resp),
+            StatusCode::NOT_FOUND => Err(SwecClientError::NotFound),
+            StatusCode::CONFLICT => Err(SwecClientError::NameConflict),
+            status => {
+                let body: ApiErrorBody = resp.json().await?;
+                let message = match body.detail {
+                    Some(detail) => format!("{}: {detail}", body.message),
+                    None => body.message,
+                };
+                Err(SwecClientError::Api {
+                    status: status.as_u16(),
+                    message,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SwecClientError {
+    /// The request couldn't be sent or the response couldn't be read.
+    Http(reqwest::Error),
+    NotFound,
+    NameConflict,
+    /// Any other error returned by the distributor.
+    Api {
+        status: u16,
+        message: String,
+    },
+}
+
+impl From<reqwest::Error> for SwecClientError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Http(value)
+    }
+}
+
+impl Display for SwecClientError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Http(e) => write!(f, "HTTP error: {e}"),
+            Self::NotFound => write!(f, "Service not found"),
+            Self::NameConflict => write!(f, "Service name conflict"),
+            Self::Api { status, message } => write!(f, "API error ({status}): {message}"),
+        }
+    }
+}
+
+impl Error for SwecClientError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Composable predicates to filter statuses.
+
+use crate::{Status, TimedStatus};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A condition on a `TimedStatus`, e.g. "down with a reason containing `timeout` in the last
+/// hour".
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Predicate {
+    IsUp,
+    IsDown,
+    /// The reason of a `Down` or `Unknown` status contains the string.
+    MessageContains(String),
+    /// The status is at or after the time.
+    After(DateTime<Utc>),
+    /// The status is at or before the time.
+    Before(DateTime<Utc>),
+    And(Box<Predicate>, Box<Predicate>),
+    Or(Box<Predicate>, Box<Predicate>),
+    Not(Box<Predicate>),
+}
+
+impl Predicate {
+    /// Whether `status` satisfies the predicate.
+    #[must_use]
+    pub fn matches(&self, status: &TimedStatus) -> bool {
+        match self {
+            Self::IsUp => status.inner.is_up(),
+            Self::IsDown => status.inner.is_down(),
+            Self::MessageContains(s) => match &status.inner {
+                Status::Down(reason) | Status::Unknown(reason) => reason.contains(s.as_str()),
+                Status::Up(_) => false,
+            },
+            Self::After(time) => status.time >= *time,
+            Self::Before(time) => status.time <= *time,
+            Self::And(a, b) => a.matches(status) && b.matches(status),
+            Self::Or(a, b) => a.matches(status) || b.matches(status),
+            Self::Not(p) => !p.matches(status),
+        }
+    }
+
+    /// Combine with `other` so that both must be satisfied.
+    #[must_use]
+    pub fn and(self, other: Self) -> Self {
+        Self::And(Box::new(self), Box::new(other))
+    }
+
+    /// Combine with `other` so that either must be satisfied.
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        Self::Or(Box::new(self), Box::new(other))
+    }
+}
+
+impl std::ops::Not for Predicate {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+}
+
+/// Keep only the statuses satisfying `pred`, in the same order.
+pub fn apply<'a>(
+    pred: &'a Predicate,
+    statuses: impl IntoIterator<Item = &'a TimedStatus> + 'a,
+) -> impl Iterator<Item = &'a TimedStatus> + 'a {
+    statuses.into_iter().filter(move |s| pred.matches(s))
+}
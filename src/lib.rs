@@ -1,21 +1,204 @@
-use chrono::{DateTime, Utc};
+pub mod client;
+pub mod filter;
+
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use std::{collections::VecDeque, fmt::Display};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    error::Error,
+    fmt::Display,
+    str::FromStr,
+};
+use utoipa::ToSchema;
+
+/// Previous specs of a service along with the time they were replaced, newest first.
+pub type SpecHistory = VecDeque<(DateTime<Utc>, ServiceSpec)>;
 
 /// A service that is being watched by a checker.
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Service {
+    /// The statuses in the order they were pushed, newest first.
     pub statuses: VecDeque<TimedStatus>,
     pub spec: ServiceSpec,
+    /// Incremented every time the spec is changed with `set_spec`.
+    #[serde(default)]
+    pub spec_version: u64,
+    /// The previous specs along with the time they were replaced, newest first.
+    #[serde(default)]
+    pub spec_history: SpecHistory,
+    /// When each of the newest statuses was received, in the same order as `statuses`. Statuses
+    /// loaded from disk have no receive time, so there can be fewer of them than statuses.
+    #[serde(skip)]
+    received_at: VecDeque<DateTime<Utc>>,
 }
 
 impl Service {
+    /// Maximum number of statuses to allocate room for upfront, whatever the cap.
+    const MAX_PREALLOCATED_STATUSES: usize = 1024;
+
     #[must_use]
     pub fn new(spec: ServiceSpec, cap: usize) -> Self {
         Self {
-            statuses: VecDeque::with_capacity(cap),
+            statuses: VecDeque::with_capacity(cap.min(Self::MAX_PREALLOCATED_STATUSES)),
             spec,
+            spec_version: 0,
+            spec_history: VecDeque::new(),
+            received_at: VecDeque::new(),
+        }
+    }
+
+    /// Replace the spec and bump `spec_version`, keeping at most `max_history` previous specs.
+    pub fn set_spec(&mut self, spec: ServiceSpec, max_history: usize) {
+        let previous = std::mem::replace(&mut self.spec, spec);
+        self.spec_history.push_front((Utc::now(), previous));
+        self.spec_history.truncate(max_history);
+        self.spec_version += 1;
+    }
+
+    /// Push a new status, dropping the oldest ones so that at most `cap` statuses are kept.
+    pub fn push_status(&mut self, status: TimedStatus, cap: usize) {
+        self.statuses.push_front(status);
+        self.statuses.truncate(cap);
+        self.received_at.push_front(Utc::now());
+        self.received_at.truncate(cap);
+    }
+
+    /// Insert a status at its place in time, e.g. one imported from another system, dropping the
+    /// oldest ones so that at most `cap` statuses are kept.
+    ///
+    /// Returns whether the status is now the current one.
+    pub fn insert_status(&mut self, status: TimedStatus, cap: usize) -> bool {
+        // Statuses are stored newest first. Those at the same time as `status` stay newer.
+        let index = self.statuses.partition_point(|s| s.time >= status.time);
+        self.statuses.insert(index, status);
+        self.statuses.truncate(cap);
+        if index <= self.received_at.len() {
+            self.received_at.insert(index, Utc::now());
+            self.received_at.truncate(cap);
+        }
+        index == 0
+    }
+
+    /// Drop the statuses older than `older_than`.
+    pub fn retention_trim(&mut self, older_than: DateTime<Utc>) {
+        self.retain_statuses(|s| s.time >= older_than);
+    }
+
+    /// Keep only the oldest status of each run of consecutive statuses of the same kind, so that
+    /// only the transitions remain.
+    pub fn compact(&mut self) {
+        let mut previous_kind = None;
+        self.retain_statuses(|s| previous_kind.replace(s.inner.kind()) != Some(s.inner.kind()));
+    }
+
+    /// Keep only the statuses for which `keep` returns `true`, along with their receive times.
+    /// `keep` is called on the oldest status first.
+    fn retain_statuses(&mut self, mut keep: impl FnMut(&TimedStatus) -> bool) {
+        let mut statuses = VecDeque::new();
+        let mut received_at = VecDeque::new();
+        // Statuses are stored newest first, and only the newest ones have a receive time.
+        for (i, status) in self.statuses.drain(..).enumerate().rev() {
+            if keep(&status) {
+                if let Some(received) = self.received_at.get(i) {
+                    received_at.push_front(*received);
+                }
+                statuses.push_front(status);
+            }
         }
+        self.statuses = statuses;
+        self.received_at = received_at;
+    }
+
+    /// Get the statuses received after `since`, newest first, whatever their own time.
+    ///
+    /// Only statuses pushed with `push_status` have a receive time, and only as long as `statuses`
+    /// isn't modified other than through the methods of `Service`.
+    pub fn received_since(&self, since: DateTime<Utc>) -> impl Iterator<Item = &TimedStatus> {
+        self.statuses
+            .iter()
+            .zip(&self.received_at)
+            .filter(move |(_, received)| **received > since)
+            .map(|(status, _)| status)
+    }
+
+    /// Get the most recently pushed status, if any.
+    #[must_use]
+    pub fn current_status(&self) -> Option<&TimedStatus> {
+        self.statuses.front()
+    }
+
+    /// Get the time and new `is_up` value of the most recent change of whether the service is up.
+    ///
+    /// Returns `None` if the service has always been up, or never been.
+    #[must_use]
+    pub fn last_transition(&self) -> Option<(DateTime<Utc>, bool)> {
+        // Statuses are stored newest first.
+        self.statuses
+            .iter()
+            .zip(self.statuses.iter().skip(1))
+            .find(|(newer, older)| newer.inner.is_up() != older.inner.is_up())
+            .map(|(newer, _)| (newer.time, newer.inner.is_up()))
+    }
+
+    /// Get the percentage of statuses since `since` that are `Status::Up`, ignoring the ones during
+    /// the maintenance window.
+    ///
+    /// Returns `None` if there are no such statuses.
+    #[must_use]
+    pub fn uptime_percent(&self, since: DateTime<Utc>) -> Option<f64> {
+        let (total, up) = self
+            .statuses
+            .iter()
+            .filter(|s| s.time >= since && !self.spec.is_in_maintenance(s.time))
+            .fold((0_u32, 0_u32), |(total, up), s| {
+                (total + 1, up + u32::from(s.inner.is_up()))
+            });
+        (total != 0).then(|| f64::from(up) / f64::from(total) * 100.0)
+    }
+
+    /// Get the total time the service has been `Status::Down` since `since`.
+    ///
+    /// A down span lasts from the first `Down` status until the next status that isn't `Down`, or
+    /// until now if the service is still down.
+    #[must_use]
+    pub fn downtime_duration(&self, since: DateTime<Utc>) -> Duration {
+        let mut downtime = Duration::zero();
+        let mut down_since = None;
+        // Statuses are stored newest first.
+        for status in self.statuses.iter().rev().filter(|s| s.time >= since) {
+            let is_down = status.inner.is_down();
+            match down_since {
+                None if is_down => down_since = Some(status.time),
+                Some(start) if !is_down => {
+                    downtime += status.time - start;
+                    down_since = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = down_since {
+            downtime += Utc::now() - start;
+        }
+        downtime
+    }
+
+    /// Summarize the statuses since `since`.
+    #[must_use]
+    pub fn summarize(&self, since: DateTime<Utc>) -> StatusSummary {
+        StatusSummary::from_statuses(self.statuses.iter().filter(|s| s.time >= since))
+    }
+
+    /// Get the incidents in the status history, oldest first. Statuses during the maintenance
+    /// window are ignored.
+    #[must_use]
+    pub fn incidents(&self) -> Vec<Incident> {
+        // Statuses are stored newest first.
+        Incident::from_statuses(
+            self.statuses
+                .iter()
+                .rev()
+                .filter(|s| !self.spec.is_in_maintenance(s.time)),
+        )
     }
 }
 
@@ -29,7 +212,51 @@ impl Display for Service {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The spec of a service along with its latest status.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct ServiceWithStatus {
+    pub spec: ServiceSpec,
+    pub latest_status: Option<TimedStatus>,
+}
+
+impl From<&Service> for ServiceWithStatus {
+    fn from(service: &Service) -> Self {
+        Self {
+            spec: service.spec.clone(),
+            latest_status: service.current_status().cloned(),
+        }
+    }
+}
+
+/// The aggregated status of all services in a group.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, Default)]
+pub struct GroupStatus {
+    pub services: Vec<String>,
+    /// Whether every service in the group is currently up.
+    pub all_up: bool,
+    /// Whether at least one service in the group is currently up.
+    pub any_up: bool,
+}
+
+impl GroupStatus {
+    /// Add a service to the group, given whether it is currently up.
+    pub fn add(&mut self, name: String, is_up: bool) {
+        self.all_up = is_up && (self.all_up || self.services.is_empty());
+        self.any_up |= is_up;
+        self.services.push(name);
+    }
+}
+
+/// Whether each service in a group is currently up.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct GroupHealth {
+    pub group: String,
+    /// Whether every service in the group is currently up.
+    pub all_up: bool,
+    pub services: BTreeMap<String, bool>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
 pub struct TimedStatus {
     pub time: DateTime<Utc>,
     pub inner: Status,
@@ -41,65 +268,1006 @@ impl Display for TimedStatus {
     }
 }
 
+impl FromStr for TimedStatus {
+    type Err = ParseStatusError;
+
+    /// Parse a status in the `<RFC 3339 time> <status>` format, e.g.
+    /// `2024-06-01T12:00:00Z down#Connection refused`. See `Status::from_str` for the status part.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (time, inner) = s
+            .split_once(' ')
+            .ok_or(ParseStatusError::MissingSeparator)?;
+        Ok(Self {
+            time: DateTime::parse_from_rfc3339(time)
+                .map_err(ParseStatusError::InvalidTime)?
+                .with_timezone(&Utc),
+            inner: inner.parse()?,
+        })
+    }
+}
+
 /// The status of a `Service`.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
 pub enum Status {
-    Up(u32),         // Latency, ms
-    Down(String),    // Reason
-    Unknown(String), // Reason
+    Up(#[serde(with = "latency_serde")] Option<u32>), // Latency, ms, if known
+    Down(String),                                     // Reason
+    Unknown(String),                                  // Reason
+}
+
+/// (De)serialization of the latency of `Status::Up`: `null` when unknown in human-readable formats
+/// such as JSON, and `u32::MAX` in binary ones, which keeps the format of existing binary state
+/// files.
+mod latency_serde {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    const UNKNOWN: u32 = u32::MAX;
+
+    pub fn serialize<S: Serializer>(
+        latency: &Option<u32>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            latency.serialize(serializer)
+        } else {
+            latency.unwrap_or(UNKNOWN).serialize(serializer)
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<u32>, D::Error> {
+        if deserializer.is_human_readable() {
+            Option::deserialize(deserializer)
+        } else {
+            u32::deserialize(deserializer).map(|latency| (latency != UNKNOWN).then_some(latency))
+        }
+    }
 }
 
 impl Status {
+    /// Whether this is `Status::Up`.
+    #[must_use]
+    pub const fn is_up(&self) -> bool {
+        matches!(self, Self::Up(_))
+    }
+
+    /// Whether this is `Status::Down`. Note that `Status::Unknown` is neither up nor down.
+    #[must_use]
+    pub const fn is_down(&self) -> bool {
+        matches!(self, Self::Down(_))
+    }
+
+    /// Get the latency of a `Status::Up`, unless it is unknown.
+    #[must_use]
+    pub const fn latency_ms(&self) -> Option<u32> {
+        match self {
+            Self::Up(latency) => *latency,
+            _ => None,
+        }
+    }
+
+    /// Get the name of the variant: `up`, `down` or `unknown`.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Up(_) => "up",
+            Self::Down(_) => "down",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+
+    /// Get the kind and the reason (or latency for `Status::Up`) of the status.
+    ///
+    /// Prefer `to_labeled_pairs`, which names the latency of `Status::Up` `latency_ms`.
     #[must_use]
     pub fn to_map(&self) -> Box<[(String, String)]> {
         Box::new(match self {
-            Self::Unknown(reason) => [
-                ("kind".to_string(), "unknown".to_string()),
+            Self::Unknown(reason) | Self::Down(reason) => [
+                ("kind".to_string(), self.kind().to_string()),
                 ("reason".to_string(), reason.to_string()),
             ],
-            Self::Down(reason) => [
-                ("kind".to_string(), "down".to_string()),
+            Self::Up(latency) => [
+                ("kind".to_string(), self.kind().to_string()),
+                ("reason".to_string(), latency_to_string(*latency)),
+            ],
+        })
+    }
+
+    /// Get the status of a command that exited with `code`: `Up` with an unknown latency if it is
+    /// 0, `Down` with `stdout` in the reason otherwise.
+    #[must_use]
+    pub fn from_exit_code(code: i32, stdout: impl Into<String>) -> Self {
+        if code == 0 {
+            Self::Up(None)
+        } else {
+            Self::Down(format!("Exit {code}: {}", stdout.into()))
+        }
+    }
+
+    /// Like `from_exit_code`, but also handles commands terminated by a signal, which are `Down`.
+    #[must_use]
+    pub fn from_exit_status(status: std::process::ExitStatus, stdout: impl Into<String>) -> Self {
+        match status.code() {
+            Some(code) => Self::from_exit_code(code, stdout),
+            None => Self::Down(format!("Terminated by signal: {}", stdout.into())),
+        }
+    }
+
+    /// Get the kind of the status along with its reason, or its latency for `Status::Up`.
+    #[must_use]
+    pub fn to_labeled_pairs(&self) -> Box<[(String, String)]> {
+        Box::new(match self {
+            Self::Unknown(reason) | Self::Down(reason) => [
+                ("kind".to_string(), self.kind().to_string()),
                 ("reason".to_string(), reason.to_string()),
             ],
-            Self::Up(d) => [
-                ("kind".to_string(), "unknown".to_string()),
-                ("reason".to_string(), d.to_string()),
+            Self::Up(latency) => [
+                ("kind".to_string(), self.kind().to_string()),
+                ("latency_ms".to_string(), latency_to_string(*latency)),
             ],
         })
     }
 }
 
+/// Get a latency as a string, `unknown` if it isn't known.
+fn latency_to_string(latency: Option<u32>) -> String {
+    latency.map_or_else(|| "unknown".to_string(), |l| l.to_string())
+}
+
 impl Display for Status {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::Up(l) => write!(f, "Up, latency {l}ms"),
+            Self::Up(Some(l)) => write!(f, "Up, latency {l}ms"),
+            Self::Up(None) => write!(f, "Up, unknown latency"),
             Self::Down(r) => write!(f, "Down: {r}"),
             Self::Unknown(r) => write!(f, "Unknown: {r}"),
         }
     }
 }
 
+impl From<http::StatusCode> for Status {
+    /// Successful (2xx) codes are `Up` with an unknown latency, the others are `Down` with the
+    /// canonical reason phrase as the reason.
+    fn from(code: http::StatusCode) -> Self {
+        if code.is_success() {
+            Self::Up(None)
+        } else {
+            Self::Down(code.canonical_reason().map_or_else(
+                || code.as_str().to_string(),
+                |reason| format!("{} {reason}", code.as_str()),
+            ))
+        }
+    }
+}
+
+impl TryFrom<u16> for Status {
+    type Error = http::status::InvalidStatusCode;
+
+    /// See `From<http::StatusCode>`.
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        http::StatusCode::from_u16(code).map(Self::from)
+    }
+}
+
+impl FromStr for Status {
+    type Err = ParseStatusError;
+
+    /// Parse a status in the `up#<message>`, `down#<reason>` or `unknown#<reason>` format. The
+    /// message of `up` is its latency in ms if it is a number, e.g. `up#42`, otherwise it is
+    /// dropped and the latency is unknown, e.g. `up#OK`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s
+            .split_once('#')
+            .ok_or(ParseStatusError::MissingSeparator)?;
+        match kind {
+            "up" => Ok(Self::Up(value.parse().ok())),
+            "down" => Ok(Self::Down(value.to_string())),
+            "unknown" => Ok(Self::Unknown(value.to_string())),
+            _ => Err(ParseStatusError::InvalidKind(kind.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseStatusError {
+    MissingSeparator,
+    InvalidKind(String),
+    InvalidTime(chrono::ParseError),
+}
+
+impl Display for ParseStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingSeparator => write!(f, "Missing separator"),
+            Self::InvalidKind(kind) => write!(f, "Invalid status kind: {kind}"),
+            Self::InvalidTime(e) => write!(f, "Invalid time: {e}"),
+        }
+    }
+}
+
+impl Error for ParseStatusError {}
+
+/// Statistics about a series of statuses.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct StatusSummary {
+    pub total: usize,
+    pub up_count: usize,
+    pub down_count: usize,
+    pub mean_latency_ms: Option<f64>,
+    pub p95_latency_ms: Option<f64>,
+    pub p99_latency_ms: Option<f64>,
+}
+
+impl StatusSummary {
+    /// Summarize `statuses`. Latencies are taken from the `Status::Up` ones whose latency is
+    /// known.
+    #[must_use]
+    pub fn from_statuses<'a>(statuses: impl IntoIterator<Item = &'a TimedStatus>) -> Self {
+        let mut total = 0;
+        let mut up_count = 0;
+        let mut down_count = 0;
+        let mut latencies = Vec::new();
+        for status in statuses {
+            total += 1;
+            match status.inner {
+                Status::Up(_) => up_count += 1,
+                Status::Down(_) => down_count += 1,
+                Status::Unknown(_) => {}
+            }
+            latencies.extend(status.inner.latency_ms());
+        }
+        latencies.sort_unstable();
+
+        let mean_latency_ms = (!latencies.is_empty()).then(|| {
+            latencies.iter().copied().map(f64::from).sum::<f64>() / latencies.len() as f64
+        });
+
+        Self {
+            total,
+            up_count,
+            down_count,
+            mean_latency_ms,
+            p95_latency_ms: percentile(&latencies, 95),
+            p99_latency_ms: percentile(&latencies, 99),
+        }
+    }
+}
+
+/// Check counts and outages over a window of statuses.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct StatusStats {
+    pub total_checks: u64,
+    pub up_checks: u64,
+    pub down_checks: u64,
+    /// Percentage of the checks that are `Status::Up`, or 0 if there are none.
+    pub uptime_percent: f64,
+    /// `None` if no `Status::Up` has a known latency.
+    pub mean_latency_ms: Option<f64>,
+    pub longest_outage_seconds: u64,
+    pub incident_count: u64,
+}
+
+impl StatusStats {
+    /// Compute the stats of `statuses`, which must be ordered oldest first. An incident that is
+    /// still ongoing is counted as lasting until `end`.
+    #[must_use]
+    pub fn from_statuses<'a>(
+        statuses: impl IntoIterator<Item = &'a TimedStatus> + Clone,
+        end: DateTime<Utc>,
+    ) -> Self {
+        let summary = StatusSummary::from_statuses(statuses.clone());
+        let incidents = Incident::from_statuses(statuses);
+        let longest_outage = incidents
+            .iter()
+            .map(|incident| incident.end.unwrap_or(end) - incident.start)
+            .max()
+            .unwrap_or_else(Duration::zero);
+
+        let uptime_percent = if summary.total == 0 {
+            0.0
+        } else {
+            summary.up_count as f64 / summary.total as f64 * 100.0
+        };
+
+        Self {
+            total_checks: summary.total as u64,
+            up_checks: summary.up_count as u64,
+            down_checks: summary.down_count as u64,
+            uptime_percent,
+            mean_latency_ms: summary.mean_latency_ms,
+            longest_outage_seconds: longest_outage.num_seconds().try_into().unwrap_or(0),
+            incident_count: incidents.len() as u64,
+        }
+    }
+}
+
+/// A run of consecutive `Status::Down` statuses.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct Incident {
+    pub start: DateTime<Utc>,
+    /// Time of the first status after the incident that isn't `Down`, or `None` if the incident is
+    /// still ongoing.
+    pub end: Option<DateTime<Utc>>,
+    pub statuses: Vec<TimedStatus>,
+}
+
+impl Incident {
+    /// Group the `Down` statuses in `statuses`, which must be ordered oldest first, into
+    /// incidents.
+    #[must_use]
+    pub fn from_statuses<'a>(statuses: impl IntoIterator<Item = &'a TimedStatus>) -> Vec<Self> {
+        let mut incidents = Vec::new();
+        let mut current: Option<Self> = None;
+        for status in statuses {
+            if status.inner.is_down() {
+                current
+                    .get_or_insert_with(|| Self {
+                        start: status.time,
+                        end: None,
+                        statuses: Vec::new(),
+                    })
+                    .statuses
+                    .push(status.clone());
+            } else if let Some(mut incident) = current.take() {
+                incident.end = Some(status.time);
+                incidents.push(incident);
+            }
+        }
+        incidents.extend(current);
+        incidents
+    }
+}
+
+/// The statuses within a fixed-length period of time.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
+pub struct StatusBucket {
+    pub bucket_start: DateTime<Utc>,
+    pub up_count: usize,
+    pub down_count: usize,
+    /// Whether any of the statuses is `Status::Up`.
+    pub any_up: bool,
+}
+
+impl StatusBucket {
+    /// Group `statuses` into consecutive buckets of length `bucket`, aligned on the Unix epoch.
+    /// Buckets are returned oldest first, and empty ones are omitted.
+    ///
+    /// # Panics
+    ///
+    /// If `bucket` is shorter than a second.
+    #[must_use]
+    pub fn from_statuses<'a>(
+        statuses: impl IntoIterator<Item = &'a TimedStatus>,
+        bucket: Duration,
+    ) -> Vec<Self> {
+        let bucket_seconds = bucket.num_seconds();
+        assert!(bucket_seconds > 0, "Buckets must last at least a second");
+        let mut buckets = BTreeMap::new();
+        for status in statuses {
+            let start = status.time.timestamp().div_euclid(bucket_seconds) * bucket_seconds;
+            let bucket = buckets.entry(start).or_insert_with(|| Self {
+                bucket_start: DateTime::from_timestamp(start, 0)
+                    .expect("Bucket start should be before the status"),
+                up_count: 0,
+                down_count: 0,
+                any_up: false,
+            });
+            bucket.up_count += usize::from(status.inner.is_up());
+            bucket.down_count += usize::from(status.inner.is_down());
+            bucket.any_up |= status.inner.is_up();
+        }
+        buckets.into_values().collect()
+    }
+}
+
+/// Get the `p`th percentile of `sorted` using the nearest-rank method.
+fn percentile(sorted: &[u32], p: usize) -> Option<f64> {
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied().map(f64::from)
+}
+
 /// Human-readable information about a `Service`.
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq, Hash, Default)]
 pub struct ServiceSpec {
+    /// Defaults to empty, which is only valid when merging into an existing spec.
+    #[serde(default)]
     pub kind: String,
+    /// Free-form tags to categorize the service, e.g. `production` or `database`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Free-form metadata, e.g. `team` => `infra` or `datacenter` => `eu-west`.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// Name of the group the service belongs to, e.g. `database`.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Maximum number of statuses kept for this service, instead of the distributor's default.
+    #[serde(default)]
+    pub max_statuses: Option<usize>,
+    /// Planned downtime, during which the service being down isn't an incident.
+    #[serde(default)]
+    pub maintenance: Option<MaintenanceWindow>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+impl ServiceSpec {
+    /// Maximum length of `kind`, in characters.
+    pub const MAX_KIND_LENGTH: usize = 256;
+    /// Maximum value of `max_statuses`, so that a client can't make the distributor keep an
+    /// unbounded number of statuses.
+    pub const MAX_STATUSES_LIMIT: usize = 100_000;
+
+    /// Check that the spec makes sense.
+    ///
+    /// # Errors
+    ///
+    /// If `kind` is empty or too long, a tag or the group is empty or contains control characters,
+    /// `max_statuses` is 0 or above `MAX_STATUSES_LIMIT`, or the maintenance window ends before it
+    /// starts.
+    pub fn validate(&self) -> Result<(), SpecValidationError> {
+        if self.kind.is_empty() {
+            return Err(SpecValidationError::EmptyKind);
+        }
+        if self.kind.chars().count() > Self::MAX_KIND_LENGTH {
+            return Err(SpecValidationError::KindTooLong);
+        }
+        if self
+            .tags
+            .iter()
+            .any(|tag| tag.is_empty() || tag.chars().any(char::is_control))
+        {
+            return Err(SpecValidationError::InvalidTag);
+        }
+        if self
+            .labels
+            .keys()
+            .any(|key| key.is_empty() || key.chars().any(char::is_control))
+        {
+            return Err(SpecValidationError::InvalidLabel);
+        }
+        if self
+            .group
+            .as_ref()
+            .is_some_and(|group| group.is_empty() || group.chars().any(char::is_control))
+        {
+            return Err(SpecValidationError::InvalidGroup);
+        }
+        if self
+            .max_statuses
+            .is_some_and(|max| max == 0 || max > Self::MAX_STATUSES_LIMIT)
+        {
+            return Err(SpecValidationError::InvalidMaxStatuses);
+        }
+        if self
+            .maintenance
+            .as_ref()
+            .is_some_and(|window| window.end < window.start)
+        {
+            return Err(SpecValidationError::InvalidMaintenanceWindow);
+        }
+        Ok(())
+    }
+
+    /// Get a copy of `self` with the fields that are set in `other` (non-empty or `Some`) replaced
+    /// by those of `other`.
+    ///
+    /// Fields can't be cleared this way, use `ServiceAction::ReplaceSpec` for that.
+    #[must_use]
+    pub fn merge(&self, other: &Self) -> Self {
+        fn pick<T: Clone>(base: &T, other: &T, is_set: bool) -> T {
+            if is_set { other } else { base }.clone()
+        }
+        Self {
+            kind: pick(&self.kind, &other.kind, !other.kind.is_empty()),
+            tags: pick(&self.tags, &other.tags, !other.tags.is_empty()),
+            labels: pick(&self.labels, &other.labels, !other.labels.is_empty()),
+            group: other.group.clone().or_else(|| self.group.clone()),
+            max_statuses: other.max_statuses.or(self.max_statuses),
+            maintenance: other
+                .maintenance
+                .clone()
+                .or_else(|| self.maintenance.clone()),
+        }
+    }
+
+    /// Whether `time` is within the maintenance window, if there is one.
+    #[must_use]
+    pub fn is_in_maintenance(&self, time: DateTime<Utc>) -> bool {
+        self.maintenance
+            .as_ref()
+            .is_some_and(|window| window.contains(time))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecValidationError {
+    EmptyKind,
+    KindTooLong,
+    InvalidTag,
+    InvalidLabel,
+    InvalidGroup,
+    InvalidMaxStatuses,
+    InvalidMaintenanceWindow,
+}
+
+impl Display for SpecValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyKind => write!(f, "Service kind is empty"),
+            Self::KindTooLong => write!(
+                f,
+                "Service kind is longer than {} characters",
+                ServiceSpec::MAX_KIND_LENGTH
+            ),
+            Self::InvalidTag => write!(f, "Tags must be non-empty and without control characters"),
+            Self::InvalidLabel => write!(
+                f,
+                "Label keys must be non-empty and without control characters"
+            ),
+            Self::InvalidGroup => {
+                write!(f, "Group must be non-empty and without control characters")
+            }
+            Self::InvalidMaxStatuses => write!(
+                f,
+                "Maximum number of statuses must be between 1 and {}",
+                ServiceSpec::MAX_STATUSES_LIMIT
+            ),
+            Self::InvalidMaintenanceWindow => {
+                write!(f, "Maintenance window ends before it starts")
+            }
+        }
+    }
+}
+
+impl Error for SpecValidationError {}
+
+/// A period of planned downtime.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MaintenanceWindow {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub reason: String,
+}
+
+impl MaintenanceWindow {
+    #[must_use]
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        (self.start..=self.end).contains(&time)
+    }
+}
+
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone)]
 pub enum ServiceAction {
     CreateService(ServiceSpec),
+    /// Merge the spec into the current one, see `ServiceSpec::merge`.
+    UpdateSpec(ServiceSpec),
+    /// Replace the whole spec, clearing the fields that aren't set in the new one.
+    ReplaceSpec(ServiceSpec),
+    SetMaintenance(MaintenanceWindow),
     DeleteService,
     AddStatus(TimedStatus),
 }
 
+impl ServiceAction {
+    /// Get the name of the variant, e.g. `add_status`.
+    #[must_use]
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::CreateService(_) => "create_service",
+            Self::UpdateSpec(_) => "update_spec",
+            Self::ReplaceSpec(_) => "replace_spec",
+            Self::SetMaintenance(_) => "set_maintenance",
+            Self::DeleteService => "delete_service",
+            Self::AddStatus(_) => "add_status",
+        }
+    }
+}
+
 impl Display for ServiceAction {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::CreateService(spec) => {
                 write!(f, "Create service with spec: {spec:?}")
             }
+            Self::UpdateSpec(spec) => write!(f, "Update spec with: {spec:?}"),
+            Self::ReplaceSpec(spec) => write!(f, "Replace spec with: {spec:?}"),
+            Self::SetMaintenance(window) => {
+                write!(f, "Set maintenance window to: {window:?}")
+            }
             Self::DeleteService => write!(f, "Delete service"),
             Self::AddStatus(s) => write!(f, "Add status: {s}"),
         }
     }
 }
+
+/// The body of the distributor's error responses.
+#[derive(Serialize, Deserialize, ToSchema, Debug, Clone, PartialEq, Eq)]
+pub struct ApiErrorBody {
+    /// Machine-readable identifier of the error, e.g. `service_not_found`.
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+}
+
+/// Conversion to and from a compact binary format.
+#[cfg(feature = "binary")]
+pub trait Binary: Serialize + serde::de::DeserializeOwned {
+    /// Serialize `self` with bincode.
+    ///
+    /// # Panics
+    ///
+    /// If `self` can't be serialized, which can't happen for the types in this crate.
+    #[must_use]
+    fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("Type should be serializable with bincode")
+    }
+
+    /// Deserialize a value serialized with `to_bytes`.
+    ///
+    /// # Errors
+    ///
+    /// If `bytes` isn't a valid serialization of `Self`.
+    fn from_bytes(bytes: &[u8]) -> bincode::Result<Self> {
+        bincode::deserialize(bytes)
+    }
+}
+
+#[cfg(feature = "binary")]
+impl Binary for Service {}
+
+#[cfg(feature = "binary")]
+impl Binary for TimedStatus {}
+
+#[cfg(feature = "binary")]
+impl Binary for Status {}
+
+#[cfg(feature = "binary")]
+impl Binary for ServiceSpec {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    /// A spec with all its optional fields set, with values depending on `suffix`.
+    fn full_spec(suffix: &str, max_statuses: usize) -> ServiceSpec {
+        ServiceSpec {
+            kind: format!("kind-{suffix}"),
+            group: Some(format!("group-{suffix}")),
+            max_statuses: Some(max_statuses),
+            maintenance: Some(MaintenanceWindow {
+                start: DateTime::UNIX_EPOCH,
+                end: DateTime::UNIX_EPOCH,
+                reason: format!("reason-{suffix}"),
+            }),
+            ..ServiceSpec::default()
+        }
+    }
+
+    /// Keep the optional fields of `spec` whose bit is set in `mask`: 1 for `group`, 2 for
+    /// `max_statuses` and 4 for `maintenance`.
+    fn with_fields(spec: ServiceSpec, mask: u8) -> ServiceSpec {
+        ServiceSpec {
+            group: spec.group.filter(|_| mask & 1 != 0),
+            max_statuses: spec.max_statuses.filter(|_| mask & 2 != 0),
+            maintenance: spec.maintenance.filter(|_| mask & 4 != 0),
+            ..spec
+        }
+    }
+
+    #[test]
+    fn merge_keeps_unset_fields() {
+        for mask in 0..8 {
+            let base = full_spec("base", 10);
+            let other = with_fields(full_spec("other", 20), mask);
+            let merged = base.merge(&other);
+            assert_eq!(merged.kind, other.kind);
+            assert_eq!(merged.group, other.group.or(base.group));
+            assert_eq!(
+                merged.max_statuses,
+                other.max_statuses.or(base.max_statuses)
+            );
+            assert_eq!(merged.maintenance, other.maintenance.or(base.maintenance));
+        }
+    }
+
+    #[test]
+    fn merge_into_empty_spec() {
+        for mask in 0..8 {
+            let base = ServiceSpec {
+                kind: "kind-base".to_string(),
+                ..ServiceSpec::default()
+            };
+            let other = with_fields(full_spec("other", 20), mask);
+            assert_eq!(base.merge(&other), other);
+        }
+    }
+
+    #[test]
+    fn merge_keeps_kind_tags_and_labels_when_empty() {
+        let base = ServiceSpec {
+            tags: vec!["production".to_string()],
+            labels: BTreeMap::from([("team".to_string(), "infra".to_string())]),
+            ..full_spec("base", 10)
+        };
+        let other = ServiceSpec::default();
+        assert_eq!(base.merge(&other), base);
+    }
+
+    fn hash(spec: &ServiceSpec) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        spec.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equal_specs_hash_equally() {
+        let new_spec = || ServiceSpec {
+            tags: vec!["production".to_string()],
+            labels: BTreeMap::from([("team".to_string(), "infra".to_string())]),
+            ..full_spec("a", 10)
+        };
+        let (a, b) = (new_spec(), new_spec());
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn parse_up_with_message() {
+        assert_eq!("up#42".parse(), Ok(Status::Up(Some(42))));
+        assert_eq!("up#OK".parse(), Ok(Status::Up(None)));
+        assert_eq!(
+            "2024-06-01T12:00:00Z up#OK"
+                .parse::<TimedStatus>()
+                .map(|s| s.inner),
+            Ok(Status::Up(None))
+        );
+    }
+
+    #[test]
+    fn compact_keeps_unknown_to_down_transitions() {
+        let mut service = Service::new(ServiceSpec::default(), 16);
+        let statuses = [
+            Status::Unknown("stale".to_string()),
+            Status::Down("refused".to_string()),
+            Status::Down("refused".to_string()),
+            Status::Up(Some(42)),
+        ];
+        for (i, inner) in statuses.into_iter().enumerate() {
+            let time = DateTime::UNIX_EPOCH + Duration::seconds(i64::try_from(i).unwrap());
+            service.push_status(TimedStatus { time, inner }, 16);
+        }
+        service.compact();
+        let kinds: Vec<_> = service.statuses.iter().map(|s| s.inner.kind()).collect();
+        assert_eq!(kinds, ["up", "down", "unknown"]);
+    }
+
+    #[test]
+    fn retention_trim_keeps_receive_times() {
+        let mut service = Service::new(ServiceSpec::default(), 16);
+        let before = Utc::now() - Duration::seconds(1);
+        for secs in [0, 2, 1] {
+            let time = DateTime::UNIX_EPOCH + Duration::seconds(secs);
+            service.push_status(
+                TimedStatus {
+                    time,
+                    inner: Status::Up(Some(42)),
+                },
+                16,
+            );
+        }
+        service.retention_trim(DateTime::UNIX_EPOCH + Duration::seconds(1));
+        assert_eq!(service.statuses.len(), 2);
+        assert_eq!(service.received_at.len(), 2);
+        assert_eq!(service.received_since(before).count(), 2);
+    }
+
+    #[test]
+    fn insert_status_keeps_time_order() {
+        let mut service = Service::new(ServiceSpec::default(), 3);
+        let at = |secs| DateTime::UNIX_EPOCH + Duration::seconds(secs);
+        assert!(service.insert_status(
+            TimedStatus {
+                time: at(10),
+                inner: Status::Up(Some(1))
+            },
+            3
+        ));
+        assert!(!service.insert_status(
+            TimedStatus {
+                time: at(5),
+                inner: Status::Up(Some(2))
+            },
+            3
+        ));
+        assert!(service.insert_status(
+            TimedStatus {
+                time: at(20),
+                inner: Status::Up(Some(3))
+            },
+            3
+        ));
+        assert!(!service.insert_status(
+            TimedStatus {
+                time: at(1),
+                inner: Status::Up(Some(4))
+            },
+            3
+        ));
+        let times: Vec<_> = service.statuses.iter().map(|s| s.time).collect();
+        assert_eq!(times, [at(20), at(10), at(5)]);
+        assert_eq!(service.received_at.len(), 3);
+    }
+
+    #[test]
+    fn zero_latency_is_not_unknown() {
+        assert_eq!(Status::Up(Some(0)).latency_ms(), Some(0));
+        assert_eq!(Status::Up(None).to_string(), "Up, unknown latency");
+        for status in [Status::Up(Some(0)), Status::Up(None)] {
+            let json = serde_json::to_string(&status).unwrap();
+            assert_eq!(serde_json::from_str::<Status>(&json).unwrap(), status);
+        }
+        assert_eq!(
+            serde_json::to_string(&Status::Up(None)).unwrap(),
+            r#"{"Up":null}"#
+        );
+    }
+
+    fn assert_clone_send_sync<T: Clone + Send + Sync>() {}
+
+    #[test]
+    fn public_types_are_clone_send_sync() {
+        assert_clone_send_sync::<Service>();
+        assert_clone_send_sync::<ServiceSpec>();
+        assert_clone_send_sync::<Status>();
+        assert_clone_send_sync::<TimedStatus>();
+        assert_clone_send_sync::<filter::Predicate>();
+        assert_clone_send_sync::<client::SwecClient>();
+    }
+
+    #[test]
+    fn predicates_combine() {
+        use filter::Predicate;
+
+        let time = DateTime::UNIX_EPOCH;
+        let up = TimedStatus {
+            time,
+            inner: Status::Up(Some(5)),
+        };
+        let timeout = TimedStatus {
+            time,
+            inner: Status::Down("timeout".to_string()),
+        };
+        let unknown = TimedStatus {
+            time,
+            inner: Status::Unknown("timeout".to_string()),
+        };
+
+        assert!(Predicate::IsUp.matches(&up));
+        assert!(!Predicate::IsDown.matches(&unknown));
+        assert!(Predicate::MessageContains("time".to_string()).matches(&unknown));
+        assert!(!Predicate::MessageContains(String::new()).matches(&up));
+
+        // Down or up, but not unknown.
+        let pred = Predicate::IsDown.or(Predicate::IsUp);
+        assert!(pred.matches(&up) && pred.matches(&timeout) && !pred.matches(&unknown));
+        // Not (down and about a timeout).
+        let pred = !Predicate::IsDown.and(Predicate::MessageContains("timeout".to_string()));
+        assert!(pred.matches(&up) && !pred.matches(&timeout) && pred.matches(&unknown));
+        // Up, or down but not about a timeout.
+        let refused = TimedStatus {
+            time,
+            inner: Status::Down("connection refused".to_string()),
+        };
+        let pred = Predicate::IsUp
+            .or(Predicate::IsDown.and(!Predicate::MessageContains("timeout".to_string())));
+        assert!(pred.matches(&up) && pred.matches(&refused));
+        assert!(!pred.matches(&timeout) && !pred.matches(&unknown));
+    }
+
+    #[test]
+    fn time_predicates_are_inclusive() {
+        use filter::Predicate;
+
+        let time = DateTime::UNIX_EPOCH + Duration::seconds(10);
+        let status = TimedStatus {
+            time,
+            inner: Status::Up(None),
+        };
+        let second = Duration::seconds(1);
+
+        assert!(Predicate::After(time).matches(&status));
+        assert!(Predicate::Before(time).matches(&status));
+        assert!(Predicate::After(time)
+            .and(Predicate::Before(time))
+            .matches(&status));
+        assert!(!Predicate::After(time + second).matches(&status));
+        assert!(!Predicate::Before(time - second).matches(&status));
+    }
+
+    /// A status `secs` seconds after the epoch.
+    fn timed(secs: i64, inner: Status) -> TimedStatus {
+        TimedStatus {
+            time: DateTime::UNIX_EPOCH + Duration::seconds(secs),
+            inner,
+        }
+    }
+
+    #[test]
+    fn percentile_uses_nearest_rank() {
+        assert_eq!(percentile(&[], 50), None);
+        assert_eq!(percentile(&[7], 0), Some(7.0));
+        assert_eq!(percentile(&[7], 99), Some(7.0));
+        let sorted: Vec<u32> = (1..=20).collect();
+        assert_eq!(percentile(&sorted, 50), Some(10.0));
+        assert_eq!(percentile(&sorted, 95), Some(19.0));
+        assert_eq!(percentile(&sorted, 99), Some(20.0));
+        assert_eq!(percentile(&sorted, 100), Some(20.0));
+    }
+
+    #[test]
+    fn incidents_group_down_runs() {
+        assert!(Incident::from_statuses(&[]).is_empty());
+
+        let statuses = [
+            timed(0, Status::Down("a".to_string())),
+            timed(5, Status::Down("b".to_string())),
+            timed(10, Status::Unknown("c".to_string())),
+            timed(20, Status::Up(None)),
+            timed(30, Status::Down("d".to_string())),
+        ];
+        let incidents = Incident::from_statuses(&statuses);
+        assert_eq!(incidents.len(), 2);
+        assert_eq!(incidents[0].start, statuses[0].time);
+        assert_eq!(incidents[0].end, Some(statuses[2].time));
+        assert_eq!(incidents[0].statuses, statuses[..2]);
+        // The last incident is still ongoing.
+        assert_eq!(incidents[1].start, statuses[4].time);
+        assert_eq!(incidents[1].end, None);
+        assert_eq!(incidents[1].statuses, statuses[4..]);
+    }
+
+    #[test]
+    fn stats_of_no_statuses() {
+        let stats = StatusStats::from_statuses(&[], DateTime::UNIX_EPOCH);
+        assert_eq!(stats.total_checks, 0);
+        assert!(stats.uptime_percent.abs() < f64::EPSILON);
+        assert_eq!(stats.mean_latency_ms, None);
+        assert_eq!(stats.longest_outage_seconds, 0);
+        assert_eq!(stats.incident_count, 0);
+    }
+
+    #[test]
+    fn stats_of_a_single_status() {
+        let statuses = [timed(0, Status::Up(Some(12)))];
+        let stats = StatusStats::from_statuses(&statuses, DateTime::UNIX_EPOCH);
+        assert_eq!(
+            (stats.total_checks, stats.up_checks, stats.down_checks),
+            (1, 1, 0)
+        );
+        assert!((stats.uptime_percent - 100.0).abs() < f64::EPSILON);
+        assert_eq!(stats.mean_latency_ms, Some(12.0));
+        assert_eq!(stats.incident_count, 0);
+    }
+
+    #[test]
+    fn stats_clamp_ongoing_incidents_to_end() {
+        let statuses = [
+            timed(0, Status::Down("a".to_string())),
+            timed(10, Status::Up(Some(1))),
+            timed(20, Status::Up(None)),
+            timed(30, Status::Down("b".to_string())),
+        ];
+        let end = DateTime::UNIX_EPOCH + Duration::seconds(60);
+        let stats = StatusStats::from_statuses(&statuses, end);
+        assert_eq!(
+            (stats.total_checks, stats.up_checks, stats.down_checks),
+            (4, 2, 2)
+        );
+        assert!((stats.uptime_percent - 50.0).abs() < f64::EPSILON);
+        // Only the known latency counts.
+        assert_eq!(stats.mean_latency_ms, Some(1.0));
+        assert_eq!(stats.incident_count, 2);
+        // The ongoing incident lasts from 30 s until `end`, longer than the closed one.
+        assert_eq!(stats.longest_outage_seconds, 30);
+    }
+}